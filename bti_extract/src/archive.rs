@@ -0,0 +1,129 @@
+// Reads the textures out of RARC archives, the .arc files GameCube and Wii games pack
+// their models and stages in. .szs files are the same archives compressed with Yaz0.
+use std::borrow::Cow;
+
+const HEADERSIZE: usize = 0x20;
+const NODESIZE: usize = 0x10;
+const ENTRYSIZE: usize = 0x14;
+const DIRECTORY: u8 = 0x02;
+
+fn truncated() -> String {
+    "truncated archive".to_string()
+}
+
+fn u16at(data: &[u8], off: usize) -> Result<u16, String> {
+    let bytes = data.get(off..off + 2).ok_or_else(truncated)?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32at(data: &[u8], off: usize) -> Result<u32, String> {
+    let bytes = data.get(off..off + 4).ok_or_else(truncated)?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn byteat(data: &[u8], off: usize) -> Result<u8, String> {
+    data.get(off).copied().ok_or_else(truncated)
+}
+
+// NUL terminated name in the string table.
+fn nameat(data: &[u8], off: usize) -> Result<String, String> {
+    let rest = data.get(off..).ok_or_else(truncated)?;
+    let len = rest.iter().position(|x| *x == 0).ok_or_else(truncated)?;
+    Ok(String::from_utf8_lossy(&rest[..len]).into_owned())
+}
+
+pub fn isyaz0(data: &[u8]) -> bool {
+    data.starts_with(b"Yaz0")
+}
+
+// Every group byte tells for the next 8 chunks whether they are a literal byte (bit set)
+// or a back reference of 3 to 0x111 bytes up to 0x1000 bytes back.
+pub fn yaz0decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    let size = u32at(data, 4)? as usize;
+    let mut res = Vec::with_capacity(size);
+    let mut src = 0x10;
+    while res.len() < size {
+        let group = byteat(data, src)?;
+        src += 1;
+        for bit in (0..8).rev() {
+            if res.len() >= size {
+                break;
+            }
+            if group & (1 << bit) != 0 {
+                res.push(byteat(data, src)?);
+                src += 1;
+                continue;
+            }
+            let (b1, b2) = (byteat(data, src)?, byteat(data, src + 1)?);
+            src += 2;
+            let distance = (((b1 & 0x0F) as usize) << 8 | b2 as usize) + 1;
+            let count = match b1 >> 4 {
+                0 => {
+                    src += 1;
+                    byteat(data, src - 1)? as usize + 0x12
+                },
+                n => n as usize + 2
+            };
+            if distance > res.len() {
+                return Err("Yaz0 data refers to bytes before its start".to_string());
+            }
+            // Copied byte by byte, the source may overlap what is being written.
+            let start = res.len() - distance;
+            for i in 0..count {
+                res.push(res[start + i]);
+            }
+        }
+    }
+    res.truncate(size);
+    Ok(res)
+}
+
+// The .bti files of an archive as (path inside the archive, file data). Files stored
+// Yaz0 compressed inside the archive are decompressed as well.
+pub fn textures(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let data = match isyaz0(data) {
+        true => Cow::Owned(yaz0decompress(data)?),
+        false => Cow::Borrowed(data)
+    };
+    let data = &data[..];
+    if !data.starts_with(b"RARC") {
+        return Err("not a RARC archive".to_string());
+    }
+    // All offsets besides the header's own are relative to the end of the header.
+    let filedata = HEADERSIZE + u32at(data, 0x0C)? as usize;
+    let nodecount = u32at(data, HEADERSIZE)? as usize;
+    let nodes = HEADERSIZE + u32at(data, HEADERSIZE + 0x04)? as usize;
+    let entries = HEADERSIZE + u32at(data, HEADERSIZE + 0x0C)? as usize;
+    let strings = HEADERSIZE + u32at(data, HEADERSIZE + 0x14)? as usize;
+    let mut res = vec![];
+    // Directories are walked from the root node, the root's own name is left out.
+    let mut visited = vec![false; nodecount];
+    let mut pending = vec![(0, String::new())];
+    while let Some((node, prefix)) = pending.pop() {
+        if node >= nodecount || visited[node] {
+            continue;
+        }
+        visited[node] = true;
+        let count = u16at(data, nodes + node * NODESIZE + 0x0A)? as usize;
+        let first = u32at(data, nodes + node * NODESIZE + 0x0C)? as usize;
+        for entry in (first..first + count).map(|i| entries + i * ENTRYSIZE) {
+            let name = nameat(data, strings + u16at(data, entry + 0x06)? as usize)?;
+            let value = u32at(data, entry + 0x08)? as usize;
+            if byteat(data, entry + 0x04)? & DIRECTORY != 0 {
+                // For directories the value is the node index.
+                if name != "." && name != ".." {
+                    pending.push((value, format!("{}{}/", prefix, name)));
+                }
+            } else if name.to_ascii_lowercase().ends_with(".bti") {
+                let size = u32at(data, entry + 0x0C)? as usize;
+                let file = data.get(filedata + value..filedata + value + size).ok_or_else(truncated)?;
+                let file = match isyaz0(file) {
+                    true => yaz0decompress(file)?,
+                    false => file.to_vec()
+                };
+                res.push((prefix.clone() + &name, file));
+            }
+        }
+    }
+    Ok(res)
+}
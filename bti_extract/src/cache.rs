@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const FNVOFFSET: u64 = 0xcbf29ce484222325;
+const FNVPRIME: u64 = 0x100000001b3;
+
+// 64 bit FNV-1a, stable across platforms and Rust versions unlike DefaultHasher.
+pub fn hash(data: &[u8]) -> u64 {
+    data.iter().fold(FNVOFFSET, |hash, byte| (hash ^ *byte as u64).wrapping_mul(FNVPRIME))
+}
+
+fn hex(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+// Content hashes an output was built from, plus the hash of the output itself so
+// outputs changed by someone else get rebuilt.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    pub source: String,
+    pub settings: String,
+    pub output: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct BuildCache {
+    #[serde(default)]
+    outputs: BTreeMap<String, CacheEntry>,
+}
+
+impl BuildCache {
+    // A missing or unreadable lock file just means everything gets rebuilt.
+    pub fn load(path: &Path) -> Self {
+        let Ok(text) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+        toml::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("ignoring {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        fs::write(path, text)
+    }
+
+    pub fn is_fresh(&self, output: &Path, source: u64, settings: u64) -> bool {
+        let Some(entry) = self.outputs.get(&output.to_string_lossy().into_owned()) else {
+            return false;
+        };
+        if entry.source != hex(source) || entry.settings != hex(settings) {
+            return false;
+        }
+        fs::read(output).is_ok_and(|data| entry.output == hex(hash(&data)))
+    }
+
+    pub fn insert(&mut self, output: &Path, source: u64, settings: u64, outputdata: &[u8]) {
+        self.outputs.insert(output.to_string_lossy().into_owned(), CacheEntry {
+            source: hex(source),
+            settings: hex(settings),
+            output: hex(hash(outputdata)),
+        });
+    }
+
+    pub fn remove(&mut self, output: &Path) {
+        self.outputs.remove(&output.to_string_lossy().into_owned());
+    }
+
+    pub fn retain<F: FnMut(&Path) -> bool>(&mut self, mut keep: F) {
+        self.outputs.retain(|output, _| keep(Path::new(output)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::TextureSettings;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn settings_change_misses() {
+        let output = env::temp_dir().join(format!("bti_cache_test_{}.bti", process::id()));
+        fs::write(&output, b"texture").unwrap();
+        let settings = TextureSettings::defaults();
+        let changed = TextureSettings { mipmaps: Some(3), ..settings.clone() };
+        let mut cache = BuildCache::default();
+        cache.insert(&output, hash(b"source"), settings.hash(), b"texture");
+        assert!(cache.is_fresh(&output, hash(b"source"), settings.hash()));
+        assert!(!cache.is_fresh(&output, hash(b"source"), changed.hash()));
+        assert!(!cache.is_fresh(&output, hash(b"edited source"), settings.hash()));
+        // Outputs changed by someone else are rebuilt too.
+        fs::write(&output, b"edited texture").unwrap();
+        assert!(!cache.is_fresh(&output, hash(b"source"), settings.hash()));
+        fs::remove_file(&output).unwrap();
+    }
+}
@@ -0,0 +1,57 @@
+use libbti::prelude::BTI;
+use libbti::diff;
+use std::fs::File;
+use std::path::Path;
+use std::process;
+
+// Prints at most this many regions, the rest is summarized.
+const MAXREGIONS: usize = 20;
+
+pub fn run(args: &[String]) {
+    let mut out = None;
+    let mut files = vec![];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--out" => out = iter.next(),
+            _ => files.push(Path::new(arg)),
+        }
+    }
+    if files.len() != 2 {
+        eprintln!("usage: bti_extract diff <left.bti> <right.bti> [--out diff.png]");
+        process::exit(2);
+    }
+    let left = BTI::read(&mut File::open(files[0]).unwrap());
+    let right = BTI::read(&mut File::open(files[1]).unwrap());
+    let res = diff::diff(&left, &right);
+    println!("--- {}\n+++ {}", files[0].display(), files[1].display());
+    for field in &res.header {
+        println!("{}: {} -> {}", field.field, field.left, field.right);
+    }
+    match &res.stats {
+        None => println!("dimensions differ, pixels were not compared"),
+        Some(_) if res.changedpixels == 0 => println!("pixels are identical"),
+        Some(stats) => {
+            let total = left.width as usize * left.height as usize;
+            println!("{} of {} pixels changed in {} region(s)", res.changedpixels, total,
+            res.regions.len());
+            for region in res.regions.iter().take(MAXREGIONS) {
+                println!("  {}x{} at ({}, {}), {} pixels", region.width, region.height,
+                region.x, region.y, region.pixels);
+            }
+            if res.regions.len() > MAXREGIONS {
+                println!("  ... and {} more", res.regions.len() - MAXREGIONS);
+            }
+            println!("{}", stats);
+        }
+    }
+    if let Some(out) = out {
+        match diff::diffimage(&left, &right) {
+            Some(img) => img.save(out).unwrap(),
+            None => eprintln!("not writing {}, the dimensions differ", out),
+        }
+    }
+    if !res.is_empty() {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,66 @@
+use libbti::prelude::{BTI, EncodeOptions, FormatChoice};
+use libbti::prelude::image::{self, RgbaImage};
+use std::fs;
+use std::io::{self, Cursor, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::cache::{self, BuildCache};
+use crate::settings::TextureSettings;
+
+pub fn loadimage(data: &[u8]) -> Result<RgbaImage, String> {
+    let reader = image::io::Reader::new(Cursor::new(data)).with_guessed_format()
+    .map_err(|e| e.to_string())?;
+    Ok(reader.decode().map_err(|e| e.to_string())?.into_rgba8())
+}
+
+// Everything that turns source images into BTIs goes through here.
+pub fn buildbti(img: RgbaImage, settings: &TextureSettings) -> Result<(BTI, Option<FormatChoice>), String> {
+    let mut bti = BTI::from(img);
+    let choice = settings.apply(&mut bti)?;
+    Ok((bti, choice))
+}
+
+// Encodes in memory so a failing encoder never leaves half written files behind.
+pub fn encodebti(bti: &BTI, options: &EncodeOptions) -> Result<Vec<u8>, String> {
+    let mut out = Cursor::new(vec![]);
+    bti.try_write_and_encode_with(&mut out, options)?;
+    Ok(out.into_inner())
+}
+
+// Encodes `source` into `output` for build and watch, returns false if the cache says the
+// output is already up to date.
+pub fn buildfile(source: &Path, output: &Path, settings: &TextureSettings,
+    cache: &mut BuildCache) -> Result<bool, String> {
+    let data = fs::read(source).map_err(|e| e.to_string())?;
+    let (sourcehash, settingshash) = (cache::hash(&data), settings.hash());
+    if cache.is_fresh(output, sourcehash, settingshash) {
+        return Ok(false);
+    }
+    let (bti, choice) = buildbti(loadimage(&data)?, settings)?;
+    if let Some(choice) = choice {
+        println!("{}: {}", source.display(), choice.reason);
+    }
+    let encoded = encodebti(&bti, &settings.encodeoptions()?)?;
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(output, &encoded).map_err(|e| e.to_string())?;
+    cache.insert(output, sourcehash, settingshash, &encoded);
+    Ok(true)
+}
+
+// Progress callback showing a percentage on stderr, silent if stderr is not a terminal.
+pub fn progress(name: String) -> impl Fn(usize, usize) + Sync {
+    let shown = AtomicUsize::new(usize::MAX);
+    let terminal = io::stderr().is_terminal();
+    move |done, total| {
+        let percent = done * 100 / total.max(1);
+        if terminal && shown.swap(percent, Ordering::Relaxed) != percent {
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "\r{}: {:3}%", name, percent);
+            if done == total {
+                let _ = writeln!(stderr);
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use libbti::prelude::{Anisotropy, BTI};
+use std::path::PathBuf;
+use std::process;
+use crate::preview;
+
+// Lists the headers of textures without decoding any pixels.
+pub fn run(args: &[String]) {
+    if args.is_empty() {
+        eprintln!("usage: bti_extract info <dir|file.bti|file.arc|file.szs>...");
+        process::exit(2);
+    }
+    let mut sources = vec![];
+    for arg in args {
+        if let Err(e) = preview::collect(&PathBuf::from(arg), &mut sources) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+    sources.sort_by(|a, b| a.path().cmp(b.path()));
+    let mut failed = 0;
+    for source in &sources {
+        match source.view(|view| describe(&view.header)) {
+            Ok(line) => println!("{}: {}", source.path().display(), line),
+            Err(e) => {
+                eprintln!("{}: {}", source.path().display(), e);
+                failed += 1;
+            }
+        }
+    }
+    println!("{} textures, {} unreadable", sources.len() - failed, failed);
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+pub fn describe(header: &BTI) -> String {
+    let mut res = format!("{:?} {}x{}, {} mips", header.format, header.width, header.height,
+    header.mipmapcount);
+    if header.palettecount > 0 {
+        res += &format!(", {} {:?} colors", header.palettecount, header.paletteformat);
+    }
+    res += &format!(", wrap {:?}/{:?}, filter {:?}/{:?}, lod {}..{}", header.wraps, header.wrapt,
+    header.minfilter, header.magfilter, header.min_lod, header.max_lod);
+    if header.lod_bias != 0.0 {
+        res += &format!(" bias {}", header.lod_bias);
+    }
+    if header.max_aniso != Anisotropy::One {
+        res += &format!(", aniso {:?}", header.max_aniso);
+    }
+    if header.mipmapcount > 1 && !header.mipmaps_enabled {
+        res += ", mipmaps disabled";
+    }
+    if header.edge_lod {
+        res += ", edge lod";
+    }
+    if header.bias_clamp {
+        res += ", bias clamp";
+    }
+    res + &format!(", alpha {}", header.alphasetting)
+}
@@ -1,37 +1,190 @@
 use libbti::prelude::BTI;
 use libbti::prelude::TextureFormats;
+use libbti::prelude::{Dither, EncodeOptions, Premultiply};
+use libbti::quality;
 use libbti::prelude::image::*;
-use std::io::Cursor;
-use std::io::Read;
 use std::path::Path;
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
+use std::process;
+use settings::TextureSettings;
+
+mod archive;
+mod cache;
+mod diff;
+mod encode;
+mod font;
+mod info;
+mod manifest;
+mod preview;
+mod settings;
+mod tile;
+mod watch;
+
+#[derive(Default)]
+struct Options {
+    // Overrides sidecar and directory settings.
+    settings: TextureSettings,
+    report: bool,
+    heatmap: bool,
+    gamealpha: bool,
+    minpsnr: Option<f64>,
+    minssim: Option<f64>,
+}
 
 fn main() {
     let envargs: Vec<String> = env::args().collect();
-    let args = (&envargs[1..]).iter().map(|x| Path::new(x))
-    .collect::<Vec<_>>();
+    match envargs.get(1).map(|x| x.as_str()) {
+        Some("build") => manifest::run(&envargs[2..]),
+        Some("diff") => diff::run(&envargs[2..]),
+        Some("info") => info::run(&envargs[2..]),
+        Some("preview") => preview::run(&envargs[2..]),
+        Some("tile") => tile::run(&envargs[2..]),
+        Some("watch") => watch::run(&envargs[2..]),
+        _ => convert(&envargs[1..]),
+    }
+}
+
+fn convert(envargs: &[String]) {
+    let mut options = Options::default();
+    let mut args = vec![];
+    let mut iter = envargs.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" => {
+                let name = iter.next().cloned().unwrap_or_default();
+                if name != "auto" {
+                    if let Err(e) = name.parse::<TextureFormats>() {
+                        eprintln!("{}", e);
+                        process::exit(2);
+                    }
+                }
+                options.settings.format = Some(name);
+            },
+            "--bleed" => options.settings.bleed = Some(true),
+            "--premultiply" => {
+                let name = iter.next().cloned().unwrap_or_default();
+                if let Err(e) = name.parse::<Premultiply>() {
+                    eprintln!("{}", e);
+                    process::exit(2);
+                }
+                options.settings.premultiply = Some(name);
+            },
+            "--dither" => {
+                let name = iter.next().cloned().unwrap_or_default();
+                if let Err(e) = name.parse::<Dither>() {
+                    eprintln!("{}", e);
+                    process::exit(2);
+                }
+                options.settings.dither = Some(name);
+            },
+            "--alpha-coverage" => {
+                let value = iter.next();
+                match value.and_then(|x| x.parse::<u8>().ok()) {
+                    Some(reference) => options.settings.alphacoverage = Some(reference),
+                    None => {
+                        eprintln!("--alpha-coverage needs an alpha reference from 0 to 255, got {}",
+                        value.map_or("nothing", |x| x.as_str()));
+                        process::exit(2);
+                    }
+                }
+            },
+            "--report" => options.report = true,
+            "--heatmap" => options.heatmap = true,
+            "--game-alpha" => options.gamealpha = true,
+            "--min-psnr" => options.minpsnr = Some(threshold(arg, iter.next())),
+            "--min-ssim" => options.minssim = Some(threshold(arg, iter.next())),
+            _ => args.push(Path::new(arg)),
+        }
+    }
+    options.settings.minpsnr = options.minpsnr;
+    let mut failed = false;
     for arg in args {
         let ext = arg.extension().unwrap().to_string_lossy();
         let stem = arg.file_stem().unwrap().to_string_lossy();
         if ext == "bti" {
             let mut file = File::open(arg).unwrap();
             let bti = BTI::read(&mut file);
-            let img = bti.into_image();
+            if let Err(e) = bti.check_alpha() {
+                eprintln!("warning: {}, {}", arg.display(), e);
+            }
+            let img = match options.gamealpha {
+                true => RgbaImage::from_raw(bti.width.into(), bti.height.into(), bti.game_pixels()).unwrap(),
+                false => bti.into_image()
+            };
             img.save_with_format(format!("{}.png", stem), ImageFormat::Png).unwrap();
         } else if ext == "png" {
-            let mut file = File::open(arg).unwrap();
-            let mut buf = vec![];
-            file.read_to_end(&mut buf).unwrap();
-            let mut reader = io::Reader::new(Cursor::new(buf));
-            reader.set_format(ImageFormat::Png);
-            let img = reader.decode().unwrap();
-            let img = img.into_rgba8();
-            let mut bti = BTI::from(img);
+            let settings = match settings::forimage(&TextureSettings::defaults(), arg) {
+                Ok(settings) => settings.merge(&options.settings),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    failed = true;
+                    continue;
+                }
+            };
+            let encodeoptions = match settings.encodeoptions() {
+                Ok(encodeoptions) => encodeoptions,
+                Err(e) => {
+                    eprintln!("{}: {}", arg.display(), e);
+                    failed = true;
+                    continue;
+                }
+            };
+            let img = encode::loadimage(&fs::read(arg).unwrap()).unwrap();
+            let bti = match encode::buildbti(img, &settings) {
+                Ok((bti, choice)) => {
+                    if let Some(choice) = choice {
+                        println!("{}: {}", arg.display(), choice.reason);
+                    }
+                    bti
+                },
+                Err(e) => {
+                    eprintln!("{}: {}", arg.display(), e);
+                    failed = true;
+                    continue;
+                }
+            };
             let path = format!("{}.bti", stem);
             let mut file = File::create(path).unwrap();
-            bti.format = TextureFormats::CMPR;
-            bti.write_and_encode(&mut file);
+            let checked = options.report || options.minpsnr.is_some() || options.minssim.is_some();
+            if !checked && !options.heatmap {
+                let progress = encode::progress(arg.display().to_string());
+                bti.write_and_encode_with(&mut file, &EncodeOptions { progress: Some(&progress), ..encodeoptions });
+                continue;
+            }
+            // The report and the heatmap share one encode.
+            let decoded = bti.write_and_encode_with_roundtrip(&mut file, &encodeoptions);
+            if checked {
+                let report = quality::compare(&bti.rgbaimagedata, &decoded, bti.width.into(), bti.height.into());
+                if options.report {
+                    println!("{} ({:?}):\n{}", arg.display(), bti.format, report);
+                }
+                let minpsnr = options.minpsnr.unwrap_or(0.0);
+                let minssim = options.minssim.unwrap_or(f64::NEG_INFINITY);
+                if !report.meets(minpsnr, minssim) {
+                    eprintln!("{}: quality below threshold (PSNR {:.2} dB, SSIM {:.4})",
+                    arg.display(), report.minpsnr(), report.minssim());
+                    failed = true;
+                }
+            }
+            if options.heatmap {
+                quality::heatmap(&bti.rgbaimagedata, &decoded, bti.width.into(), bti.height.into())
+                .save_with_format(format!("{}.diff.png", stem), ImageFormat::Png).unwrap();
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+// The value of --min-psnr or --min-ssim, anything that isn't a number is a usage error.
+fn threshold(flag: &str, value: Option<&String>) -> f64 {
+    match value.and_then(|x| x.parse::<f64>().ok()).filter(|x| !x.is_nan()) {
+        Some(value) => value,
+        None => {
+            eprintln!("{} needs a number, got {}", flag, value.map_or("nothing", |x| x.as_str()));
+            process::exit(2);
         }
     }
 }
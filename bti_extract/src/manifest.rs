@@ -0,0 +1,148 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use crate::cache::BuildCache;
+use crate::encode;
+use crate::settings::TextureSettings;
+
+pub const DEFAULTMANIFEST: &str = "textures.toml";
+
+// A manifest looks like this, paths are relative to the manifest:
+//
+//     outdir = "build"
+//
+//     [defaults]
+//     format = "CMPR"
+//
+//     [[glob]]
+//     pattern = "ui/**/*.png"
+//     format = "RGB5A3"
+//
+//     [[texture]]
+//     source = "stage/ground.png"
+//     output = "stage/ground.bti"
+//     mipmaps = 4
+//
+// Settings are taken from the defaults, then every matching glob in order, then the
+// texture itself. Without `output` the texture ends up at its source path inside of
+// `outdir` with a .bti extension.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default)]
+    pub outdir: Option<PathBuf>,
+    #[serde(default)]
+    pub defaults: TextureSettings,
+    #[serde(default, rename = "glob")]
+    pub globs: Vec<GlobRule>,
+    #[serde(default, rename = "texture")]
+    pub textures: Vec<TextureEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GlobRule {
+    pub pattern: String,
+    #[serde(flatten)]
+    pub settings: TextureSettings,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TextureEntry {
+    pub source: PathBuf,
+    #[serde(default)]
+    pub output: Option<PathBuf>,
+    #[serde(flatten)]
+    pub settings: TextureSettings,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn settings(&self, texture: &TextureEntry) -> TextureSettings {
+        let source = texture.source.to_string_lossy().replace('\\', "/");
+        let mut res = TextureSettings::defaults().merge(&self.defaults);
+        for rule in &self.globs {
+            if globmatch(rule.pattern.as_bytes(), source.as_bytes()) {
+                res = res.merge(&rule.settings);
+            }
+        }
+        res.merge(&texture.settings)
+    }
+
+    pub fn output(&self, texture: &TextureEntry) -> PathBuf {
+        match &texture.output {
+            Some(output) => output.clone(),
+            None => self.outdir.clone().unwrap_or_default().join(texture.source.with_extension("bti"))
+        }
+    }
+}
+
+// `*` and `?` stay within one path component, `**` matches across components.
+pub fn globmatch(pattern: &[u8], path: &[u8]) -> bool {
+    if let Some(rest) = pattern.strip_prefix(b"**") {
+        let rest = rest.strip_prefix(b"/").unwrap_or(rest);
+        if rest.is_empty() {
+            return true;
+        }
+        return (0..=path.len()).filter(|&i| i == 0 || path[i - 1] == b'/')
+        .any(|i| globmatch(rest, &path[i..]));
+    }
+    match pattern.first() {
+        None => path.is_empty(),
+        Some(b'*') => (0..=path.len()).take_while(|&i| i == 0 || path[i - 1] != b'/')
+            .any(|i| globmatch(&pattern[1..], &path[i..])),
+        Some(b'?') => path.first().is_some_and(|c| *c != b'/') && globmatch(&pattern[1..], &path[1..]),
+        Some(c) => path.first() == Some(c) && globmatch(&pattern[1..], &path[1..]),
+    }
+}
+
+fn lockpath(manifest: &Path) -> PathBuf {
+    manifest.with_extension("lock")
+}
+
+pub fn run(args: &[String]) {
+    let mut force = false;
+    let mut path = PathBuf::from(DEFAULTMANIFEST);
+    for arg in args {
+        match arg.as_str() {
+            "--force" => force = true,
+            _ => path = PathBuf::from(arg),
+        }
+    }
+    let manifest = Manifest::load(&path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    let root = path.parent().unwrap_or(Path::new("")).to_path_buf();
+    let lock = lockpath(&path);
+    let mut cache = if force { BuildCache::default() } else { BuildCache::load(&lock) };
+    let outputs = manifest.textures.iter().map(|x| root.join(manifest.output(x))).collect::<Vec<_>>();
+    cache.retain(|output| outputs.iter().any(|x| x == output));
+    let (mut built, mut fresh, mut failed) = (0, 0, 0);
+    for (texture, output) in manifest.textures.iter().zip(&outputs) {
+        let settings = manifest.settings(texture);
+        match encode::buildfile(&root.join(&texture.source), output, &settings, &mut cache) {
+            Ok(true) => {
+                println!("built {}", output.display());
+                built += 1;
+            },
+            Ok(false) => fresh += 1,
+            Err(e) => {
+                eprintln!("error: {}: {}", texture.source.display(), e);
+                cache.remove(output);
+                failed += 1;
+            }
+        }
+    }
+    if let Err(e) = cache.save(&lock) {
+        eprintln!("could not write {}: {}", lock.display(), e);
+    }
+    println!("{} built, {} up to date, {} failed", built, fresh, failed);
+    if failed > 0 {
+        process::exit(1);
+    }
+}
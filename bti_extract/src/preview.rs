@@ -0,0 +1,219 @@
+use libbti::prelude::{BTI, BtiView, MappedFile};
+use libbti::prelude::image::{imageops, Rgba, RgbaImage};
+use libbti::prelude::image::imageops::FilterType;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use crate::{archive, font};
+
+const BACKGROUND: Rgba<u8> = Rgba([0x20, 0x20, 0x20, 0xFF]);
+const TEXTCOLOR: Rgba<u8> = Rgba([0xE0, 0xE0, 0xE0, 0xFF]);
+const CHECKERLIGHT: [u8; 3] = [0xCC, 0xCC, 0xCC];
+const CHECKERDARK: [u8; 3] = [0x99, 0x99, 0x99];
+const CHECKERSIZE: u32 = 8;
+const PADDING: u32 = 4;
+const LABELLINES: u32 = 2;
+
+struct Options {
+    out: String,
+    columns: u32,
+    rows: u32,
+    cell: u32,
+    // Shows alpha the way the alpha setting makes the game use it.
+    gamealpha: bool,
+}
+
+// A texture file, or a texture read out of an archive.
+pub enum Source {
+    File(PathBuf),
+    // The path is the archive's followed by the one inside it.
+    Archived(PathBuf, Vec<u8>),
+}
+
+impl Source {
+    pub fn path(&self) -> &Path {
+        match self {
+            Source::File(path) | Source::Archived(path, _) => path,
+        }
+    }
+
+    // Runs `f` on a view of the texture, files are memory mapped.
+    pub fn view<T>(&self, f: impl FnOnce(BtiView) -> T) -> Result<T, String> {
+        match self {
+            Source::File(path) => {
+                let file = MappedFile::open(path).map_err(|e| e.to_string())?;
+                file.view().map(f)
+            },
+            Source::Archived(_, data) => BtiView::parse(data).map(f)
+        }
+    }
+}
+
+struct Entry {
+    name: String,
+    bti: BTI,
+}
+
+pub fn run(args: &[String]) {
+    let mut options = Options {
+        out: "preview".to_string(),
+        columns: 8,
+        rows: 6,
+        cell: 128,
+        gamealpha: false,
+    };
+    let mut inputs = vec![];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut number = || iter.next().and_then(|x| x.parse().ok()).filter(|x| *x > 0);
+        match arg.as_str() {
+            "--out" => options.out = iter.next().cloned().unwrap_or(options.out),
+            "--columns" => options.columns = number().unwrap_or(options.columns),
+            "--rows" => options.rows = number().unwrap_or(options.rows),
+            "--cell" => options.cell = number().unwrap_or(options.cell),
+            "--game-alpha" => options.gamealpha = true,
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+    if inputs.is_empty() {
+        eprintln!("usage: bti_extract preview <dir|file.bti|file.arc|file.szs>... [--out prefix] [--columns n] [--rows n] [--cell px] [--game-alpha]");
+        process::exit(2);
+    }
+    let mut sources = vec![];
+    for input in &inputs {
+        if let Err(e) = collect(input, &mut sources) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+    sources.sort_by(|a, b| a.path().cmp(b.path()));
+    // Textures are decoded one at a time and drawn right away, only the sheet stays in memory.
+    let perpage = (options.columns * options.rows) as usize;
+    let (mut page, mut count) = (0, 0);
+    let mut sheet = newsheet(&options);
+    for entry in sources.iter().filter_map(load) {
+        draw(&mut sheet, count, &entry, &options);
+        count += 1;
+        if count == perpage {
+            save(&sheet, page, count, &options);
+            (page, count) = (page + 1, 0);
+            sheet = newsheet(&options);
+        }
+    }
+    if count > 0 {
+        save(&sheet, page, count, &options);
+    }
+}
+
+// Directories are searched recursively, the textures in .arc and .szs archives are
+// read into memory.
+pub fn collect(path: &Path, sources: &mut Vec<Source>) -> Result<(), String> {
+    let extension = path.extension().map(|x| x.to_string_lossy().to_ascii_lowercase());
+    if path.is_dir() {
+        let error = |e: io::Error| format!("{}: {}", path.display(), e);
+        for entry in fs::read_dir(path).map_err(error)? {
+            collect(&entry.map_err(error)?.path(), sources)?;
+        }
+    } else if extension.as_deref() == Some("bti") {
+        sources.push(Source::File(path.to_path_buf()));
+    } else if matches!(extension.as_deref(), Some("arc" | "rarc" | "szs")) {
+        match fs::read(path).map_err(|e| e.to_string()).and_then(|data| archive::textures(&data)) {
+            Ok(textures) => sources.extend(textures.into_iter().map(|(name, data)| {
+                Source::Archived(path.join(name), data)
+            })),
+            Err(e) => eprintln!("skipping {}, {}", path.display(), e),
+        }
+    }
+    Ok(())
+}
+
+fn load(source: &Source) -> Option<Entry> {
+    let path = source.path();
+    // Broken or unsupported textures shouldn't stop the whole sheet.
+    match source.view(|x| x.try_decode()).and_then(|x| x) {
+        Ok(bti) => {
+            if let Err(e) = bti.check_alpha() {
+                eprintln!("warning: {}, {}", path.display(), e);
+            }
+            Some(Entry {
+                name: path.file_name().unwrap().to_string_lossy().into_owned(),
+                bti,
+            })
+        },
+        Err(e) => {
+            eprintln!("skipping {}, {}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn checkerboard(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        let [r, g, b] = match (x / CHECKERSIZE + y / CHECKERSIZE) % 2 {
+            0 => CHECKERLIGHT,
+            _ => CHECKERDARK,
+        };
+        Rgba([r, g, b, 0xFF])
+    })
+}
+
+fn thumbnail(bti: &BTI, cell: u32, gamealpha: bool) -> RgbaImage {
+    let img = match gamealpha {
+        true => RgbaImage::from_raw(bti.width.into(), bti.height.into(), bti.game_pixels()).unwrap(),
+        false => bti.clone().into_image()
+    };
+    let (w, h) = img.dimensions();
+    if w == 0 || h == 0 {
+        return img;
+    }
+    let scale = (cell as f64 / w as f64).min(cell as f64 / h as f64);
+    let (tw, th) = (((w as f64 * scale) as u32).max(1), ((h as f64 * scale) as u32).max(1));
+    // Keep pixels crisp when blowing up small textures.
+    let filter = if scale >= 1.0 { FilterType::Nearest } else { FilterType::Triangle };
+    imageops::resize(&img, tw, th, filter)
+}
+
+fn label(entry: &Entry, chars: usize) -> [String; LABELLINES as usize] {
+    let bti = &entry.bti;
+    let mut name = entry.name.clone();
+    if name.chars().count() > chars {
+        name = name.chars().take(chars.saturating_sub(2)).collect::<String>() + "..";
+    }
+    let info = format!("{:?} {}x{} M{}", bti.format, bti.width, bti.height, bti.mipmapcount);
+    [name, info]
+}
+
+fn cellsize(options: &Options) -> (u32, u32) {
+    (options.cell + PADDING * 2, options.cell + PADDING * 3 + LABELLINES * font::LINEHEIGHT)
+}
+
+// Room for a full page, `save` crops it to the cells in use.
+fn newsheet(options: &Options) -> RgbaImage {
+    let (cellw, cellh) = cellsize(options);
+    RgbaImage::from_pixel(options.columns * cellw, options.rows * cellh, BACKGROUND)
+}
+
+fn draw(sheet: &mut RgbaImage, index: usize, entry: &Entry, options: &Options) {
+    let (cellw, cellh) = cellsize(options);
+    let (cx, cy) = ((index as u32 % options.columns) * cellw, (index as u32 / options.columns) * cellh);
+    let thumb = thumbnail(&entry.bti, options.cell, options.gamealpha);
+    let (tx, ty) = (cx + PADDING + (options.cell - thumb.width()) / 2,
+        cy + PADDING + (options.cell - thumb.height()) / 2);
+    let mut backing = checkerboard(thumb.width(), thumb.height());
+    imageops::overlay(&mut backing, &thumb, 0, 0);
+    imageops::replace(sheet, &backing, tx as i64, ty as i64);
+    let texty = cy + options.cell + PADDING * 2;
+    for (line, text) in label(entry, font::fits(options.cell)).iter().enumerate() {
+        font::drawtext(sheet, text, cx + PADDING, texty + line as u32 * font::LINEHEIGHT, TEXTCOLOR);
+    }
+}
+
+fn save(sheet: &RgbaImage, page: usize, count: usize, options: &Options) {
+    let (cellw, cellh) = cellsize(options);
+    let columns = options.columns.min(count as u32);
+    let rows = (count as u32).div_ceil(options.columns);
+    let path = format!("{}_{:03}.png", options.out, page);
+    imageops::crop_imm(sheet, 0, 0, columns * cellw, rows * cellh).to_image().save(&path).unwrap();
+    println!("{} ({} textures)", path, count);
+}
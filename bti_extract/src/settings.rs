@@ -0,0 +1,158 @@
+use libbti::prelude::{BTI, EncodeOptions, FormatChoice, TextureFormats, alphasetting};
+use libbti::mipmap;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::cache;
+
+pub const AUTOMINPSNR: f64 = 35.0;
+// Settings shared by every image of a directory.
+pub const DIRCONFIG: &str = "bti.toml";
+
+// Texture settings as found in sidecar files, unset fields keep the defaults.
+// Enum values use the variant names, e.g. `format = "CMPR"` or `wraps = "Repeat"`,
+// `format = "auto"` picks the smallest format reaching `minpsnr`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextureSettings {
+    pub format: Option<String>,
+    pub paletteformat: Option<String>,
+    pub wraps: Option<String>,
+    pub wrapt: Option<String>,
+    pub minfilter: Option<String>,
+    pub magfilter: Option<String>,
+    pub minpsnr: Option<f64>,
+    // Number of mip levels including the full size image.
+    pub mipmaps: Option<u8>,
+    // Passed on as EncodeOptions, e.g. `premultiply = "Unpremultiply"` for premultiplied sources
+    // or `dither = "FloydSteinberg"`.
+    pub bleed: Option<bool>,
+    pub premultiply: Option<String>,
+    pub alphacoverage: Option<u8>,
+    pub dither: Option<String>,
+}
+
+macro_rules! merge_fields {
+    ($res:expr, $over:expr, [$($field:ident),+]) => {
+        $(
+            if $over.$field.is_some() {
+                $res.$field = $over.$field.clone();
+            }
+        )+
+    };
+}
+
+impl TextureSettings {
+    // What the CLI uses when nothing is configured.
+    pub fn defaults() -> Self {
+        Self {
+            format: Some("CMPR".to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // Hashes the TOML form, unlike Debug output it doesn't change between Rust versions.
+    pub fn hash(&self) -> u64 {
+        cache::hash(toml::to_string(self).unwrap().as_bytes())
+    }
+
+    // Returns these settings with every field set in `over` replaced.
+    pub fn merge(&self, over: &TextureSettings) -> TextureSettings {
+        let mut res = self.clone();
+        merge_fields!(res, over, [format, paletteformat, wraps, wrapt, minfilter, magfilter, minpsnr,
+            mipmaps, bleed, premultiply, alphacoverage, dither]);
+        res
+    }
+
+    // The options to encode with, without progress reporting.
+    pub fn encodeoptions(&self) -> Result<EncodeOptions<'static>, String> {
+        let mut res = EncodeOptions {
+            bleed: self.bleed.unwrap_or(false),
+            alphacoverage: self.alphacoverage,
+            ..Default::default()
+        };
+        if let Some(name) = &self.premultiply {
+            res.premultiply = name.parse()?;
+        }
+        if let Some(name) = &self.dither {
+            res.dither = name.parse()?;
+        }
+        Ok(res)
+    }
+
+    // Applies the settings, the pixel data has to be set already. Returns the
+    // automatic format choice if there was one.
+    pub fn apply(&self, bti: &mut BTI) -> Result<Option<FormatChoice>, String> {
+        if let Some(name) = &self.paletteformat {
+            bti.paletteformat = name.parse()?;
+        }
+        if let Some(name) = &self.wraps {
+            bti.wraps = name.parse()?;
+        }
+        if let Some(name) = &self.wrapt {
+            bti.wrapt = name.parse()?;
+        }
+        if let Some(name) = &self.minfilter {
+            bti.minfilter = name.parse()?;
+        }
+        if let Some(name) = &self.magfilter {
+            bti.magfilter = name.parse()?;
+        }
+        if let Some(mipmaps) = self.mipmaps {
+            bti.mipmapcount = mipmaps.clamp(1, mipmap::maxmipmaps(bti.width, bti.height));
+            bti.mipmaps_enabled = bti.mipmapcount > 1;
+            bti.max_lod = (bti.mipmapcount - 1) as f32;
+        }
+        match self.format.as_deref() {
+            Some("auto") => Ok(Some(bti.select_format(self.minpsnr.unwrap_or(AUTOMINPSNR)))),
+            Some(name) => {
+                bti.format = name.parse()?;
+                if matches!(bti.format, TextureFormats::C4 | TextureFormats::C8) && !bti.generate_palette() {
+                    return Err(format!("too many colors for {:?}", bti.format));
+                }
+                bti.alphasetting = alphasetting(bti.format, bti.paletteformat, &bti.rgbaimagedata) as u8;
+                Ok(None)
+            },
+            None => Ok(None)
+        }
+    }
+}
+
+// `foo.png` is configured by `foo.bti.toml` next to it.
+pub fn sidecarpath(image: &Path) -> PathBuf {
+    let stem = image.file_stem().unwrap_or_default().to_string_lossy();
+    image.with_file_name(format!("{}.bti.toml", stem))
+}
+
+// Directory config overlaid with the sidecar, both are optional.
+pub fn forimage(defaults: &TextureSettings, image: &Path) -> Result<TextureSettings, String> {
+    let mut res = defaults.clone();
+    let dirconfig = image.with_file_name(DIRCONFIG);
+    if dirconfig.is_file() {
+        res = res.merge(&TextureSettings::load(&dirconfig)?);
+    }
+    let sidecar = sidecarpath(image);
+    if sidecar.is_file() {
+        res = res.merge(&TextureSettings::load(&sidecar)?);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_is_stable() {
+        assert_eq!(TextureSettings::defaults().hash(), cache::hash(b"format = \"CMPR\"\n"));
+        let a: TextureSettings = toml::from_str("format = \"RGB5A3\"\nmipmaps = 3").unwrap();
+        let b: TextureSettings = toml::from_str("mipmaps = 3\nformat = \"RGB5A3\"").unwrap();
+        assert_eq!(a.hash(), b.hash());
+        assert_ne!(a.hash(), TextureSettings { mipmaps: Some(4), ..a.clone() }.hash());
+    }
+}
@@ -0,0 +1,59 @@
+use libbti::prelude::{BTI, Sampler};
+use libbti::prelude::image::RgbaImage;
+use std::fs::File;
+use std::path::Path;
+use std::process;
+
+struct Options {
+    out: Option<String>,
+    tiles: (u32, u32),
+    size: Option<(u32, u32)>,
+    scale: f32,
+}
+
+// "3x2" or "3" for both.
+fn pair(arg: Option<&String>) -> Option<(u32, u32)> {
+    let arg = arg?;
+    let (x, y) = arg.split_once('x').unwrap_or((arg, arg));
+    Some((x.parse().ok()?, y.parse().ok()?)).filter(|(x, y)| *x > 0 && *y > 0)
+}
+
+// Renders a texture repeated NxM times through the CPU sampler, so wrap seams and
+// bad mip levels show up without loading the game. Scaling the output down makes
+// the sampler pick smaller levels the way the hardware would.
+pub fn run(args: &[String]) {
+    let mut options = Options {
+        out: None,
+        tiles: (3, 3),
+        size: None,
+        scale: 1.0,
+    };
+    let mut input = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--out" => options.out = iter.next().cloned(),
+            "--tiles" => options.tiles = pair(iter.next()).unwrap_or(options.tiles),
+            "--size" => options.size = pair(iter.next()).or(options.size),
+            "--scale" => options.scale = iter.next().and_then(|x| x.parse().ok()).filter(|x| *x > 0.0)
+            .unwrap_or(options.scale),
+            _ => input = Some(Path::new(arg)),
+        }
+    }
+    let Some(input) = input else {
+        eprintln!("usage: bti_extract tile <file.bti> [--out file.png] [--tiles NxM] [--size WxH | --scale f]");
+        process::exit(2);
+    };
+    let bti = BTI::read(&mut File::open(input).unwrap());
+    let sampler = Sampler::new(&bti);
+    let (tilesu, tilesv) = options.tiles;
+    let (width, height) = options.size.unwrap_or_else(|| {
+        let width = (bti.width as f32 * tilesu as f32 * options.scale).round().max(1.0);
+        let height = (bti.height as f32 * tilesv as f32 * options.scale).round().max(1.0);
+        (width as u32, height as u32)
+    });
+    let pixels = sampler.render(width, height, tilesu as f32, tilesv as f32);
+    let path = options.out.unwrap_or_else(|| format!("{}_tiled.png", input.file_stem().unwrap().to_string_lossy()));
+    RgbaImage::from_raw(width, height, pixels).unwrap().save(&path).unwrap();
+    println!("{} ({}x{} tiles, {}x{} pixels)", path, tilesu, tilesv, width, height);
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use crate::cache::BuildCache;
+use crate::encode;
+use crate::settings::{self, TextureSettings};
+
+const SOURCEEXTENSIONS: [&str; 5] = ["png", "tga", "bmp", "tif", "tiff"];
+// Build cache in the output directory, so restarting doesn't rebuild everything.
+const LOCKFILE: &str = "bti_watch.lock";
+
+struct Options {
+    source: PathBuf,
+    out: Option<PathBuf>,
+    interval: Duration,
+    debounce: Duration,
+}
+
+// Modification times of an image and the settings files that apply to it.
+type Stamp = [Option<(SystemTime, u64)>; 3];
+
+struct Tracked {
+    stamp: Stamp,
+    // Set while the file is still changing, it is built once it settles.
+    pending: Option<Instant>,
+}
+
+pub fn run(args: &[String]) {
+    let mut options = Options {
+        source: PathBuf::new(),
+        out: None,
+        interval: Duration::from_millis(250),
+        debounce: Duration::from_millis(400),
+    };
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut millis = || iter.next().and_then(|x| x.parse().ok()).map(Duration::from_millis);
+        match arg.as_str() {
+            "--out" => options.out = iter.next().map(PathBuf::from),
+            "--interval" => options.interval = millis().unwrap_or(options.interval),
+            "--debounce" => options.debounce = millis().unwrap_or(options.debounce),
+            _ => options.source = PathBuf::from(arg),
+        }
+    }
+    if !options.source.is_dir() {
+        eprintln!("usage: bti_extract watch <dir> [--out dir] [--interval ms] [--debounce ms]");
+        process::exit(2);
+    }
+    println!("watching {}", options.source.display());
+    let mut tracked: HashMap<PathBuf, Tracked> = HashMap::new();
+    let lock = options.out.as_ref().unwrap_or(&options.source).join(LOCKFILE);
+    let mut cache = BuildCache::load(&lock);
+    loop {
+        let mut changed = false;
+        let mut images = vec![];
+        collect(&options.source, &mut images);
+        tracked.retain(|path, _| images.contains(path));
+        let now = Instant::now();
+        for image in images {
+            let current = stamp(&image);
+            let entry = tracked.entry(image.clone()).or_insert(Tracked { stamp: current, pending: Some(now) });
+            if entry.stamp != current {
+                entry.stamp = current;
+                entry.pending = Some(now);
+            }
+            if entry.pending.is_some_and(|since| now.duration_since(since) >= options.debounce) {
+                entry.pending = None;
+                let output = outputpath(&options, &image);
+                let res = settings::forimage(&TextureSettings::defaults(), &image)
+                .and_then(|settings| encode::buildfile(&image, &output, &settings, &mut cache));
+                match res {
+                    Ok(true) => {
+                        println!("built {}", output.display());
+                        changed = true;
+                    },
+                    Ok(false) => {},
+                    Err(e) => {
+                        cache.remove(&output);
+                        changed = true;
+                        eprintln!("error: {}: {}", image.display(), e);
+                    }
+                }
+            }
+        }
+        if changed {
+            if let Err(e) = cache.save(&lock) {
+                eprintln!("could not write {}: {}", lock.display(), e);
+            }
+        }
+        thread::sleep(options.interval);
+    }
+}
+
+fn collect(dir: &Path, images: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect(&path, images);
+        } else if path.extension().is_some_and(|ext| {
+            SOURCEEXTENSIONS.iter().any(|x| ext.eq_ignore_ascii_case(x))
+        }) {
+            images.push(path);
+        }
+    }
+}
+
+fn filestamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+fn stamp(image: &Path) -> Stamp {
+    [filestamp(image), filestamp(&settings::sidecarpath(image)),
+    filestamp(&image.with_file_name(settings::DIRCONFIG))]
+}
+
+fn outputpath(options: &Options, image: &Path) -> PathBuf {
+    let name = format!("{}.bti", image.file_stem().unwrap().to_string_lossy());
+    match &options.out {
+        Some(out) => {
+            let relative = image.strip_prefix(&options.source).unwrap_or(image);
+            out.join(relative).with_file_name(name)
+        },
+        None => image.with_file_name(name)
+    }
+}
@@ -0,0 +1,88 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use libbti::prelude::*;
+
+const SIZE: u16 = 1024;
+
+type Expander = fn(&[u8], &mut [u8], PixelLayout);
+type CmprPalette = fn(u16, u16) -> [[u8; 4]; 4];
+
+// Noise with a few smooth areas so CMPR and the palettes see realistic data.
+fn testimage() -> BTI {
+    let mut state = 0x2545F491u32;
+    let img = image::RgbaImage::from_fn(SIZE.into(), SIZE.into(), |x, y| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        let noise = state.to_le_bytes();
+        image::Rgba([(x / 4) as u8 ^ noise[0] & 0x1F, (y / 4) as u8, noise[1], noise[2] | 0x80])
+    });
+    BTI::from(img)
+}
+
+fn decode(c: &mut Criterion) {
+    decodegroup(c, "decode", &|run| run());
+    // The same decodes on a single thread, to compare against the block rows decoded in parallel.
+    #[cfg(feature = "rayon")]
+    {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        decodegroup(c, "decode_serial", &|run| pool.install(run));
+    }
+}
+
+fn decodegroup(c: &mut Criterion, name: &str, runner: &dyn Fn(&mut (dyn FnMut() + Send))) {
+    let source = testimage();
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements(SIZE as u64 * SIZE as u64));
+    group.sample_size(20);
+    let formats = [TextureFormats::I4, TextureFormats::I8, TextureFormats::IA4, TextureFormats::IA8,
+        TextureFormats::RGB565, TextureFormats::RGB5A3, TextureFormats::RGBA32, TextureFormats::CMPR,
+        TextureFormats::C4, TextureFormats::C8];
+    let mut dst = vec![0u8; SIZE as usize * SIZE as usize * 4];
+    for format in formats {
+        let mut bti = source.clone();
+        bti.format = format;
+        if matches!(format, TextureFormats::C4 | TextureFormats::C8) {
+            let count = if format == TextureFormats::C4 { 16 } else { 256 };
+            bti.imagepalette = Palette { palettedata: (0..count * 2).map(|x| (x * 37) as u8).collect() };
+            bti.palettecount = count as u16;
+        }
+        let src = bti.encodedata();
+        group.bench_function(format!("{:?}", format), |b| b.iter(|| runner(&mut || match format {
+            TextureFormats::C4 | TextureFormats::C8 => decode_indexed_into(format, black_box(&src), SIZE,
+                SIZE, &bti.imagepalette.palettedata, bti.paletteformat, &mut dst),
+            _ => decode_into(format, black_box(&src), SIZE, SIZE, &mut dst)
+        })));
+    }
+    group.finish();
+}
+
+// The SIMD pixel expansion against the scalar versions, over a texture worth of pixels.
+fn kernels(c: &mut Criterion) {
+    let mut bti = testimage();
+    bti.format = TextureFormats::RGB565;
+    let src = bti.encodedata();
+    let mut group = c.benchmark_group("kernels");
+    group.throughput(Throughput::Elements(SIZE as u64 * SIZE as u64));
+    group.sample_size(20);
+    let mut dst = [0u8; 16];
+    let expanders: [(&str, Expander); 4] = [("rgb565x4", rgb565x4),
+        ("rgb565x4scalar", rgb565x4scalar), ("rgb5a3x4", rgb5a3x4), ("rgb5a3x4scalar", rgb5a3x4scalar)];
+    for (name, expand) in expanders {
+        group.bench_function(name, |b| b.iter(|| for pixels in black_box(&src).chunks_exact(8) {
+            expand(pixels, &mut dst, PixelLayout::Rgba);
+            black_box(&dst);
+        }));
+    }
+    // One palette per 4x4 block.
+    let palettes: [(&str, CmprPalette); 2] = [("cmprpalette", cmprpalette),
+        ("cmprpalettescalar", cmprpalettescalar)];
+    for (name, palette) in palettes {
+        group.bench_function(name, |b| b.iter(|| for colors in black_box(&src).chunks_exact(4).step_by(4) {
+            black_box(palette(u16::from_be_bytes([colors[0], colors[1]]), u16::from_be_bytes([colors[2], colors[3]])));
+        }));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode, kernels);
+criterion_main!(benches);
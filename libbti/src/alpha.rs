@@ -0,0 +1,48 @@
+use crate::prelude::{AlphaSetting, PaletteFormats, TextureFormats};
+
+// Alpha values below this are transparent in OneBit textures, like CMPR's 1 bit alpha.
+pub const ALPHATHRESHOLD: u8 = 0x80;
+
+// What the RGBA pixels need: Opaque if every alpha is 255, OneBit if they are 0 or 255.
+pub fn alphaneeded(data: &[u8]) -> AlphaSetting {
+    let mut res = AlphaSetting::Opaque;
+    for pix in data.chunks_exact(4) {
+        match pix[3] {
+            0xFF => (),
+            0x00 => res = res.max(AlphaSetting::OneBit),
+            _ => return AlphaSetting::Full
+        }
+    }
+    res
+}
+
+// The most alpha `format` can store, palette formats depend on the palette.
+pub fn formatalpha(format: TextureFormats, paletteformat: PaletteFormats) -> AlphaSetting {
+    match format {
+        TextureFormats::I4 | TextureFormats::I8 | TextureFormats::RGB565 => AlphaSetting::Opaque,
+        TextureFormats::CMPR => AlphaSetting::OneBit,
+        TextureFormats::IA4 | TextureFormats::IA8 | TextureFormats::RGB5A3 | TextureFormats::RGBA32 => AlphaSetting::Full,
+        TextureFormats::C4 | TextureFormats::C8 | TextureFormats::C14X2 => match paletteformat {
+            PaletteFormats::RGB565 => AlphaSetting::Opaque,
+            PaletteFormats::IA8 | PaletteFormats::RGB5A3 => AlphaSetting::Full,
+        }
+    }
+}
+
+// The setting matching the pixels once they are stored in `format`.
+pub fn alphasetting(format: TextureFormats, paletteformat: PaletteFormats, data: &[u8]) -> AlphaSetting {
+    alphaneeded(data).min(formatalpha(format, paletteformat))
+}
+
+// Changes the alpha of RGBA pixels to what the game uses with `setting`: Opaque ignores
+// the alpha channel and OneBit only knows transparent and opaque.
+pub fn applyalpha(data: &mut [u8], setting: AlphaSetting) {
+    for pix in data.chunks_exact_mut(4) {
+        pix[3] = match setting {
+            AlphaSetting::Opaque => 0xFF,
+            AlphaSetting::OneBit if pix[3] < ALPHATHRESHOLD => 0x00,
+            AlphaSetting::OneBit => 0xFF,
+            AlphaSetting::Full => pix[3],
+        };
+    }
+}
@@ -1,14 +1,32 @@
-use std::io::{Read, Seek, Write};
+#[cfg(feature = "std")]
+use std::io::{Seek, Write};
+#[cfg(feature = "binrw")]
+use std::io::{Read, SeekFrom};
+use alloc::{format, string::String, vec, vec::Vec};
 use crate::enums::*;
 use crate::palette::Palette;
 use crate::*;
 use crate::imadedataformat::Converter;
 use crate::encoders::*;
+use crate::mipmap;
+#[cfg(feature = "image")]
+use crate::quality::{self, QualityReport};
+#[cfg(feature = "image")]
+use crate::selector::{self, FormatChoice};
+use crate::options::EncodeOptions;
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "binrw")]
 use binrw::prelude::*;
+#[cfg(feature = "binrw")]
 use binrw::Endian;
+#[cfg(feature = "binrw")]
 use binrw::WriteOptions;
+#[cfg(feature = "image")]
 use image::*;
 
+#[cfg(feature = "binrw")]
 pub fn writer_options(endian: Option<Endian>) -> WriteOptions {
     let endian = match endian {
         Some(e) => e,
@@ -17,7 +35,10 @@ pub fn writer_options(endian: Option<Endian>) -> WriteOptions {
     WriteOptions::new(endian)
 }
 
+pub const HEADERSIZE: i32 = 0x20;
+
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BTI {
     pub format: TextureFormats,
     pub alphasetting: u8,
@@ -29,52 +50,157 @@ pub struct BTI {
     pub paletteformat: PaletteFormats,
     pub palettecount: u16,
     pub palettedataoffset: i32,
-    pub embeddedpaletteoffset: i32,
+    // Whether the sampler uses the mip levels at all, set for textures with more than one.
+    pub mipmaps_enabled: bool,
+    pub edge_lod: bool,
+    pub bias_clamp: bool,
+    pub max_aniso: Anisotropy,
     pub minfilter: FilterMode,
     pub magfilter: FilterMode,
-    pub unknown2: i16,
+    // LOD range sampled by the hardware, stored in 1/8 steps.
+    pub min_lod: f32,
+    pub max_lod: f32,
     pub mipmapcount: u8,
     pub unknown3: u8,
-    pub lodbias: i16,
+    // Stored in 1/100 steps.
+    pub lod_bias: f32,
     pub imagedataoffset: i32,
     pub imagepalette: Palette,
-    pub rgbaimagedata: Vec<u8>
+    // RGBA8 pixels of the first image level, row by row.
+    pub rgbaimagedata: Vec<u8>,
+    // Encoded bytes of all levels as they were read. They are written again instead of
+    // re-encoding as long as `rawhash` matches, see is_modified. Not serialized, a
+    // deserialized BTI is always encoded from its pixels.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub rawimagedata: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub rawhash: u64
 }
 
 impl BTI {
+    #[cfg(feature = "binrw")]
     pub fn read<R: Read + Seek>(reader: &mut R) -> Self {
-        let mut res = Self::default();
-        res.format = reader.read_ne().unwrap();
-        res.alphasetting = reader.read_ne().unwrap();
-        res.width = reader.read_be().unwrap();
-        res.height = reader.read_be().unwrap();
-        res.wraps = reader.read_ne().unwrap();
-        res.wrapt = reader.read_ne().unwrap();
-        res.palettesenabled = reader.read_type::<u8>(Endian::NATIVE).unwrap() != 0;
-        res.paletteformat = reader.read_ne().unwrap();
-        res.palettecount = reader.read_be().unwrap();
-        res.palettedataoffset = reader.read_be().unwrap();
-        res.embeddedpaletteoffset = reader.read_be().unwrap();
-        res.minfilter = reader.read_ne().unwrap();
-        res.magfilter = reader.read_ne().unwrap();
-        res.unknown2 = reader.read_be().unwrap();
-        res.mipmapcount = reader.read_be().unwrap();
-        res.unknown3 = reader.read_be().unwrap();
-        res.lodbias = reader.read_be().unwrap();
-        res.imagedataoffset = reader.read_be().unwrap();
-        res.imagepalette = Palette::read(reader, res.palettecount);
-        res.rgbaimagedata = decoders::decode(reader, &res);
+        let start = reader.stream_position().unwrap();
+        let mut res = Self::read_header(reader);
+        // Offsets are relative to the start of the header.
+        if res.palettecount > 0 {
+            reader.seek(SeekFrom::Start(start + res.palettedataoffset as u64)).unwrap();
+            res.imagepalette = Palette::read(reader, res.palettecount);
+        }
+        reader.seek(SeekFrom::Start(start + res.imagedataoffset as u64)).unwrap();
+        let size = (0..res.mipmapcount.max(1)).map(|level| {
+            let (width, height) = mipmap::levelsize(res.width, res.height, level);
+            decoders::encodedsize(res.format, width, height)
+        }).sum::<usize>();
+        // Files with fewer levels than announced still decode, they just can't be copied.
+        let mut raw = vec![];
+        reader.take(size as u64).read_to_end(&mut raw).unwrap();
+        res.rgbaimagedata = decoders::decodebytes(&raw, &res);
+        if raw.len() == size {
+            res.keep_raw(raw);
+        }
         res
     }
 
+    // Remembers the encoded bytes the current pixels were decoded from.
+    pub fn keep_raw(&mut self, data: Vec<u8>) {
+        self.rawimagedata = data;
+        self.rawhash = self.content_hash();
+    }
+
+    // FNV-1a over everything that goes into the encoded image data.
+    pub fn content_hash(&self) -> u64 {
+        let header = [self.format as u8, self.paletteformat as u8, self.mipmapcount];
+        [&header[..], &self.width.to_be_bytes(), &self.height.to_be_bytes(),
+            &self.imagepalette.palettedata, &self.rgbaimagedata].iter()
+        .flat_map(|x| x.iter())
+        .fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+    }
+
+    // True if there are no raw bytes or the pixels, format, size, level count or palette
+    // changed since they were read.
+    pub fn is_modified(&self) -> bool {
+        self.rawimagedata.is_empty() || self.rawhash != self.content_hash()
+    }
+
+    pub fn alpha_setting(&self) -> Result<AlphaSetting, String> {
+        match self.alphasetting {
+            0..=2 => Ok(self.alphasetting.into()),
+            n => Err(format!("unknown alpha setting {}", n))
+        }
+    }
+
+    // Errors if the alpha setting contradicts the format or the pixels, i.e. if the game
+    // would ignore alpha the pixels need or expect alpha the format can't store.
+    pub fn check_alpha(&self) -> Result<(), String> {
+        let setting = self.alpha_setting()?;
+        if setting > alpha::formatalpha(self.format, self.paletteformat) {
+            return Err(format!("marked {:?}, but {:?} textures can't store that much alpha", setting,
+                self.format));
+        }
+        let needed = alpha::alphasetting(self.format, self.paletteformat, &self.rgbaimagedata);
+        if needed > setting {
+            return Err(format!("{:?} alpha pixels in a texture marked {:?}", needed, setting));
+        }
+        Ok(())
+    }
+
+    // The first level with the alpha the game uses, see applyalpha. Unknown settings keep it.
+    pub fn game_pixels(&self) -> Vec<u8> {
+        let mut res = self.rgbaimagedata.clone();
+        if let Ok(setting) = self.alpha_setting() {
+            alpha::applyalpha(&mut res, setting);
+        }
+        res
+    }
+
+    #[cfg(feature = "binrw")]
+    // Only reads the 0x20 header bytes, palette and pixels stay empty.
+    pub fn read_header<R: Read + Seek>(reader: &mut R) -> Self {
+        Self::try_read_header(reader).unwrap()
+    }
+
+    #[cfg(feature = "binrw")]
+    // Like read_header, but unknown enum values or missing bytes are errors.
+    pub fn try_read_header<R: Read + Seek>(reader: &mut R) -> BinResult<Self> {
+        Ok(Self {
+            format: reader.read_ne()?,
+            alphasetting: reader.read_ne()?,
+            width: reader.read_be()?,
+            height: reader.read_be()?,
+            wraps: reader.read_ne()?,
+            wrapt: reader.read_ne()?,
+            palettesenabled: reader.read_type::<u8>(Endian::NATIVE)? != 0,
+            paletteformat: reader.read_ne()?,
+            palettecount: reader.read_be()?,
+            palettedataoffset: reader.read_be()?,
+            mipmaps_enabled: reader.read_type::<u8>(Endian::NATIVE)? != 0,
+            edge_lod: reader.read_type::<u8>(Endian::NATIVE)? != 0,
+            bias_clamp: reader.read_type::<u8>(Endian::NATIVE)? != 0,
+            max_aniso: reader.read_ne()?,
+            minfilter: reader.read_ne()?,
+            magfilter: reader.read_ne()?,
+            min_lod: reader.read_type::<i8>(Endian::NATIVE)? as f32 / 8.0,
+            max_lod: reader.read_type::<i8>(Endian::NATIVE)? as f32 / 8.0,
+            mipmapcount: reader.read_be()?,
+            unknown3: reader.read_be()?,
+            lod_bias: reader.read_type::<i16>(Endian::Big)? as f32 / 100.0,
+            imagedataoffset: reader.read_be()?,
+            ..Default::default()
+        })
+    }
+
+    #[cfg(feature = "image")]
     pub fn into_image(self) -> RgbaImage {
         self.into()
     }
 
+    #[cfg(feature = "image")]
     pub fn from_image(img: RgbaImage) -> Self {
         Self::from(img)
     }
 
+    #[cfg(feature = "binrw")]
     pub fn write_header<W: Write + Seek>(&self, writer: &mut W) {
         let options = &writer_options(None);
         (self.format as u8).write_options(writer, options, ()).unwrap();
@@ -89,83 +215,286 @@ impl BTI {
         }.write_options(writer, options, ()).unwrap();
         (self.paletteformat as u8).write_options(writer, options, ()).unwrap();
         (self.palettecount as i16).write_options(writer, options, ()).unwrap();
-        0i32.write_options(writer, options, ()).unwrap();
-        self.embeddedpaletteoffset.write_options(writer, options, ()).unwrap();
+        let (paletteoffset, imageoffset) = self.dataoffsets();
+        paletteoffset.write_options(writer, options, ()).unwrap();
+        u8::from(self.mipmaps_enabled).write_options(writer, options, ()).unwrap();
+        u8::from(self.edge_lod).write_options(writer, options, ()).unwrap();
+        u8::from(self.bias_clamp).write_options(writer, options, ()).unwrap();
+        (self.max_aniso as u8).write_options(writer, options, ()).unwrap();
         (self.minfilter as u8).write_options(writer, options, ()).unwrap();
         (self.magfilter as u8).write_options(writer, options, ()).unwrap();
-        self.unknown2.write_options(writer, options, ()).unwrap();
+        ((self.min_lod * 8.0).round() as i8).write_options(writer, options, ()).unwrap();
+        ((self.max_lod * 8.0).round() as i8).write_options(writer, options, ()).unwrap();
         self.mipmapcount.write_options(writer, options, ()).unwrap();
-        self.lodbias.write_options(writer, options, ()).unwrap();
-        0i32.write_options(writer, options, ()).unwrap();
+        self.unknown3.write_options(writer, options, ()).unwrap();
+        ((self.lod_bias * 100.0).round() as i16).write_options(writer, options, ()).unwrap();
+        imageoffset.write_options(writer, options, ()).unwrap();
+    }
+
+    // The palette directly follows the header, the image data starts at the next
+    // 32 byte boundary after it.
+    pub fn dataoffsets(&self) -> (i32, i32) {
+        if self.palettecount == 0 {
+            return (0, HEADERSIZE);
+        }
+        let paletteend = HEADERSIZE + self.palettecount as i32 * 2;
+        (HEADERSIZE, (paletteend + 0x1F) & !0x1F)
     }
 
+    #[cfg(feature = "std")]
+    pub fn write_palette<W: Write + Seek>(&self, writer: &mut W) {
+        if self.palettecount == 0 {
+            return;
+        }
+        let (paletteoffset, imageoffset) = self.dataoffsets();
+        writer.write_all(&self.imagepalette.palettedata).unwrap();
+        let padding = imageoffset - paletteoffset - self.imagepalette.palettedata.len() as i32;
+        writer.write_all(&vec![0u8; padding as usize]).unwrap();
+    }
+
+    // Fills in the palette for C4/C8 textures from the pixel data, returns false if
+    // the image has too many colors to fit.
+    pub fn generate_palette(&mut self) -> bool {
+        let maxcolors = match self.format {
+            TextureFormats::C4 => 16,
+            TextureFormats::C8 => 256,
+            _ => return false
+        };
+        match Palette::generate(&self.rgbaimagedata, self.paletteformat, maxcolors) {
+            Some(palette) => {
+                self.palettesenabled = true;
+                self.palettecount = palette.count() as u16;
+                self.imagepalette = palette;
+                true
+            },
+            None => false
+        }
+    }
+
+    #[cfg(feature = "std")]
     pub fn encode<W: Write + Seek>(&self, writer: &mut W) {
-        let Self {
-            width, height, rgbaimagedata, ..
-        } = self;
-        let width = *width as i32;
-        let height = *height as i32;
-        let data = match self.format {
-            TextureFormats::I4 => I4::convertto(rgbaimagedata, width, height),
-            TextureFormats::I8 => I8::convertto(rgbaimagedata, width, height),
-            TextureFormats::IA4 => IA4::convertto(rgbaimagedata, width, height),
-            TextureFormats::IA8 => IA8::convertto(rgbaimagedata, width, height),
-            TextureFormats::RGB565 => RGB565::convertto(rgbaimagedata, width, height),
-            TextureFormats::RGB5A3 => RGB5A3::convertto(rgbaimagedata, width, height),
-            TextureFormats::RGBA32 => RGBA32::convertto(rgbaimagedata, width, height),
-            TextureFormats::CMPR => CMPR::convertto(rgbaimagedata, width, height),
-            _ => unimplemented!("Other Texture Formats are currently not supported.")
+        self.encode_with(writer, &EncodeOptions::default());
+    }
+
+    #[cfg(feature = "std")]
+    pub fn encode_with<W: Write + Seek>(&self, writer: &mut W, options: &EncodeOptions) {
+        writer.write_all(&self.encodedata_with(options)).unwrap();
+    }
+
+    // Encodes all `mipmapcount` levels, the smaller ones are generated from the pixel data.
+    pub fn encodedata(&self) -> Vec<u8> {
+        self.encodedata_with(&EncodeOptions::default())
+    }
+
+    // Levels are encoded in parallel with the rayon feature, the output is the same
+    // no matter how many threads are used.
+    // Unmodified textures that were read from a file give back the original bytes, unless
+    // `options` change the pixels first.
+    pub fn encodedata_with(&self, options: &EncodeOptions) -> Vec<u8> {
+        if !self.is_modified() && !options.preprocesses() {
+            return self.rawimagedata.clone();
+        }
+        let levels = match options.preprocesses() {
+            true => {
+                let mut data = self.rgbaimagedata.clone();
+                preprocess::prepare(&mut data, self.width.into(), self.height.into(), options);
+                let mut levels = mipmap::generatemips(&data, self.width, self.height, self.mipmapcount);
+                preprocess::preparemips(&mut levels, options);
+                for (level, data) in levels.iter_mut().enumerate() {
+                    let (width, height) = mipmap::levelsize(self.width, self.height, level as u8);
+                    dither::dither(data, width.into(), height.into(), self.format, self.paletteformat,
+                        &self.imagepalette, options.dither);
+                }
+                levels
+            },
+            false => mipmap::generatemips(&self.rgbaimagedata, self.width, self.height, self.mipmapcount)
+        };
+        let blockheight = decoders::blocksize(self.format).1;
+        let total = (0..levels.len()).map(|level| {
+            (mipmap::levelsize(self.width, self.height, level as u8).1 as usize).div_ceil(blockheight)
+        }).sum();
+        let done = AtomicUsize::new(0);
+        let rowdone = || {
+            let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+            if let Some(progress) = options.progress {
+                progress(done, total);
+            }
         };
+        let encode = |(level, data): (usize, &Vec<u8>)| {
+            let (width, height) = mipmap::levelsize(self.width, self.height, level as u8);
+            self.encodelevel_with(data, width, height, &rowdone)
+        };
+        #[cfg(feature = "rayon")]
+        let encoded: Vec<Vec<u8>> = levels.par_iter().enumerate().map(encode).collect();
+        #[cfg(not(feature = "rayon"))]
+        let encoded: Vec<Vec<u8>> = levels.iter().enumerate().map(encode).collect();
+        encoded.concat()
+    }
+
+    // Errors for the textures `encodedata_with` would panic on, unmodified ones give back
+    // their original bytes and always work.
+    pub fn check_encodable(&self, options: &EncodeOptions) -> Result<(), String> {
+        if !self.is_modified() && !options.preprocesses() {
+            return Ok(());
+        }
+        match self.format {
+            TextureFormats::C14X2 => Err("C14X2 textures can't be encoded".into()),
+            TextureFormats::C4 | TextureFormats::C8 if self.palettecount == 0 =>
+                Err(format!("{:?} textures need a palette, see generate_palette", self.format)),
+            _ => Ok(())
+        }
+    }
+
+    pub fn encodelevel(&self, data: &[u8], width: u16, height: u16) -> Vec<u8> {
+        self.encodelevel_with(data, width, height, &|| {})
+    }
+
+    // `rowdone` is called whenever a row of blocks is finished.
+    pub fn encodelevel_with(&self, data: &[u8], width: u16, height: u16, rowdone: &(dyn Fn() + Sync)) -> Vec<u8> {
+        let width = width as i32;
+        let height = height as i32;
+        match self.format {
+            TextureFormats::I4 => I4::convertwith(data, width, height, rowdone),
+            TextureFormats::I8 => I8::convertwith(data, width, height, rowdone),
+            TextureFormats::IA4 => IA4::convertwith(data, width, height, rowdone),
+            TextureFormats::IA8 => IA8::convertwith(data, width, height, rowdone),
+            TextureFormats::RGB565 => RGB565::convertwith(data, width, height, rowdone),
+            TextureFormats::RGB5A3 => RGB5A3::convertwith(data, width, height, rowdone),
+            TextureFormats::RGBA32 => RGBA32::convertwith(data, width, height, rowdone),
+            TextureFormats::CMPR => CMPR::convertwith(data, width, height, rowdone),
+            TextureFormats::C4 | TextureFormats::C8 => {
+                assert!(self.palettecount > 0, "C4/C8 textures need a palette, see generate_palette.");
+                let indices = self.imagepalette.indices(data, self.paletteformat)
+                .into_iter().flat_map(|x| [x, 0, 0, 0]).collect::<Vec<u8>>();
+                match self.format {
+                    TextureFormats::C4 => C4::convertwith(&indices, width, height, rowdone),
+                    _ => C8::convertwith(&indices, width, height, rowdone)
+                }
+            },
+            _ => unimplemented!("Other Texture Formats are currently not supported.")
+        }
+    }
+
+    // Decodes what `encode` would write, i.e. the pixels the game is going to see.
+    pub fn roundtrip(&self) -> Vec<u8> {
+        let data = self.encodedata();
+        self.decode_for_game(&data)
+    }
+
+    // Decodes the first level of encoded `data` with the alpha the format gives it in game.
+    // I4 and I8 decode the intensity into alpha, but they are as opaque as RGB565.
+    pub fn decode_for_game(&self, data: &[u8]) -> Vec<u8> {
+        let mut res = decoders::decodebytes(data, self);
+        alpha::applyalpha(&mut res, alpha::formatalpha(self.format, self.paletteformat));
+        res
+    }
+
+    // Like `roundtrip`, but the RGBA pixels of every level, largest first.
+    pub fn decode_levels(&self) -> Vec<Vec<u8>> {
+        let data = self.encodedata();
+        let mut start = 0;
+        (0..self.mipmapcount.max(1)).map(|level| {
+            let (width, height) = mipmap::levelsize(self.width, self.height, level);
+            let mut dst = vec![0u8; width as usize * height as usize * 4];
+            match self.format {
+                TextureFormats::C4 | TextureFormats::C8 => decoders::decode_indexed_into(self.format, &data[start..],
+                    width, height, &self.imagepalette.palettedata, self.paletteformat, &mut dst),
+                format => decoders::decode_into(format, &data[start..], width, height, &mut dst)
+            }
+            start += decoders::encodedsize(self.format, width, height);
+            dst
+        }).collect()
+    }
+
+    #[cfg(feature = "image")]
+    pub fn quality_report(&self) -> QualityReport {
+        let decoded = self.roundtrip();
+        quality::compare(&self.rgbaimagedata, &decoded, self.width.into(), self.height.into())
+    }
+
+    #[cfg(feature = "image")]
+    pub fn quality_heatmap(&self) -> RgbaImage {
+        let decoded = self.roundtrip();
+        quality::heatmap(&self.rgbaimagedata, &decoded, self.width.into(), self.height.into())
+    }
+
+    #[cfg(feature = "image")]
+    pub fn encode_with_report<W: Write + Seek>(&self, writer: &mut W) -> QualityReport {
+        let decoded = self.encode_with_roundtrip(writer, &EncodeOptions::default());
+        quality::compare(&self.rgbaimagedata, &decoded, self.width.into(), self.height.into())
+    }
+
+    #[cfg(feature = "std")]
+    // Writes what `encode_with` writes and returns the first level decoded from it like
+    // `roundtrip` does, so a report and a heatmap don't need to encode again.
+    pub fn encode_with_roundtrip<W: Write + Seek>(&self, writer: &mut W, options: &EncodeOptions) -> Vec<u8> {
+        let data = self.encodedata_with(options);
         writer.write_all(&data).unwrap();
+        self.decode_for_game(&data)
+    }
+
+    #[cfg(feature = "image")]
+    pub fn select_format(&mut self, minpsnr: f64) -> FormatChoice {
+        let choice = selector::selectformat(self, minpsnr);
+        choice.apply(self);
+        choice
     }
 
+    #[cfg(feature = "binrw")]
     pub fn write_and_encode<W: Write + Seek>(&self, writer: &mut W) {
+        self.write_and_encode_with(writer, &EncodeOptions::default());
+    }
+
+    #[cfg(feature = "binrw")]
+    pub fn write_and_encode_with<W: Write + Seek>(&self, writer: &mut W, options: &EncodeOptions) {
         self.write_header(writer);
-        self.encode(writer);
+        self.write_palette(writer);
+        self.encode_with(writer, options);
+    }
+
+    #[cfg(feature = "binrw")]
+    // Like `write_and_encode_with`, but nothing is written for textures that can't be encoded.
+    pub fn try_write_and_encode_with<W: Write + Seek>(&self, writer: &mut W, options: &EncodeOptions) -> Result<(), String> {
+        self.check_encodable(options)?;
+        self.write_and_encode_with(writer, options);
+        Ok(())
+    }
+
+    #[cfg(all(feature = "image", feature = "binrw"))]
+    pub fn write_and_encode_with_report<W: Write + Seek>(&self, writer: &mut W) -> QualityReport {
+        self.write_header(writer);
+        self.write_palette(writer);
+        self.encode_with_report(writer)
+    }
+
+    #[cfg(feature = "binrw")]
+    pub fn write_and_encode_with_roundtrip<W: Write + Seek>(&self, writer: &mut W, options: &EncodeOptions) -> Vec<u8> {
+        self.write_header(writer);
+        self.write_palette(writer);
+        self.encode_with_roundtrip(writer, options)
     }
 }
 
-impl Into<RgbaImage> for BTI {
-    fn into(self) -> RgbaImage {
-        let mut res = RgbaImage::from_raw(self.width.into(),
-        self.height.into(), self.rgbaimagedata)
-        .unwrap();
-        // Converts BGRA pixels into RGBA pixels.
-        for w in 0..res.width() {
-            for h in 0..res.height() {
-                let mut pix = res[(w, h)];
-                let b = pix[0];
-                let r = pix[2];
-                pix[0] = r;
-                pix[2] = b;
-                res[(w, h)] = pix;
-            }
-        }
-        res
+#[cfg(feature = "image")]
+impl From<BTI> for RgbaImage {
+    fn from(bti: BTI) -> RgbaImage {
+        RgbaImage::from_raw(bti.width.into(), bti.height.into(), bti.rgbaimagedata).unwrap()
     }
 }
 
+#[cfg(feature = "image")]
 impl From<RgbaImage> for BTI {
     fn from(img: RgbaImage) -> Self {
-        let mut res = Self::default();
-        res.format = TextureFormats::CMPR;
-        res.magfilter = FilterMode::Linear;
-        res.minfilter = FilterMode::Linear;
-        res.width = img.width() as u16;
-        res.height = img.height() as u16;
-        let mut copy = img.clone();
-        drop(img);
-        for x in 0..copy.width() {
-            for y in 0..copy.height() {
-                let mut pix = copy[(x, y)];
-                let b = pix[2];
-                let r = pix[0];
-                pix[0] = b;
-                pix[2] = r;
-                copy[(x, y)] = pix;
-            }
-        }
-        res.rgbaimagedata = copy.into_raw();
+        let mut res = Self {
+            format: TextureFormats::CMPR,
+            magfilter: FilterMode::Linear,
+            minfilter: FilterMode::Linear,
+            width: img.width() as u16,
+            height: img.height() as u16,
+            mipmapcount: 1,
+            ..Default::default()
+        };
+        res.rgbaimagedata = img.into_raw();
         decectandsetsittingformat(&mut res);
         res
     }
@@ -173,32 +502,52 @@ impl From<RgbaImage> for BTI {
 
 pub fn decectandsetsittingformat(res: &mut BTI) {
     let mut is_gray = true;
-    let mut complex_alpha = false;
-    let mut has_alpha = false;
-    for i in 0..(res.rgbaimagedata.len() / 4) {
-        let r = res.rgbaimagedata[i * 4];
-        let b = res.rgbaimagedata[i * 4 + 1];
-        let g = res.rgbaimagedata[i * 4 + 2];
-        let a = res.rgbaimagedata[i * 4 + 3];
-        if is_gray && (r != g || g != b || b != r) {
+    for pix in res.rgbaimagedata.chunks_exact(4) {
+        if pix[0] != pix[1] || pix[1] != pix[2] {
             is_gray = false;
+            break;
         }
-        if a != 255 {
-            has_alpha = true;
-            if a != 0 {
-                complex_alpha = true;
-            }
-        }
-    }
-    if is_gray {
-        res.format = TextureFormats::I8;
-    } else if complex_alpha {
-        res.format = TextureFormats::RGB5A3;
-    } else {
-        res.format = TextureFormats::CMPR;
-    }
-    if has_alpha {
-        res.alphasetting = 0x1;
     }
+    let needed = alpha::alphaneeded(&res.rgbaimagedata);
+    // I8 has no alpha, gray pixels with alpha go into IA8 instead.
+    res.format = match (is_gray, needed) {
+        (true, AlphaSetting::Opaque) => TextureFormats::I8,
+        (true, _) => TextureFormats::IA8,
+        (false, AlphaSetting::Full) => TextureFormats::RGB5A3,
+        (false, _) => TextureFormats::CMPR,
+    };
+    res.alphasetting = needed as u8;
 }
 
+#[cfg(all(test, feature = "binrw"))]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // A CMPR header with every byte from 0x10 to 0x1B set to something different.
+    const HEADER: [u8; 0x20] = [
+        0x0E, 0x01, 0x00, 0x40, 0x00, 0x20, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x01, 0x01, 0x00, 0x02, 0x05, 0x01, 0x08, 0x10, 0x03, 0x00, 0xFF, 0x9C, 0x00, 0x00, 0x00, 0x20,
+    ];
+
+    #[test]
+    fn header_roundtrip() {
+        let bti = BTI::try_read_header(&mut Cursor::new(&HEADER)).unwrap();
+        assert_eq!(bti.format, TextureFormats::CMPR);
+        assert_eq!((bti.width, bti.height), (64, 32));
+        assert_eq!((bti.wraps, bti.wrapt), (WrapNodes::Repeat, WrapNodes::MirroredRepeat));
+        assert!(bti.mipmaps_enabled);
+        assert!(bti.edge_lod);
+        assert!(!bti.bias_clamp);
+        assert_eq!(bti.max_aniso, Anisotropy::Four);
+        assert_eq!((bti.minfilter, bti.magfilter), (FilterMode::LinearMipmapLinear, FilterMode::Linear));
+        assert_eq!((bti.min_lod, bti.max_lod), (1.0, 2.0));
+        assert_eq!(bti.mipmapcount, 3);
+        assert_eq!(bti.lod_bias, -1.0);
+        assert_eq!(bti.imagedataoffset, 0x20);
+
+        let mut written = Cursor::new(vec![]);
+        bti.write_header(&mut written);
+        assert_eq!(written.into_inner(), HEADER);
+    }
+}
@@ -0,0 +1,295 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+use crate::prelude::{Anisotropy, BTI, FilterMode, PaletteFormats, TextureFormats, WrapNodes, alphasetting,
+    decectandsetsittingformat, maxmipmaps};
+
+// Largest width and height GX textures can have.
+pub const MAXSIZE: u32 = 1024;
+
+// Why `BtiBuilder::build` rejected the settings.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    // Width and height are outside of 1x1 to MAXSIZE x MAXSIZE.
+    Size { width: u32, height: u32 },
+    // The pixel buffer doesn't hold width * height RGBA pixels.
+    PixelCount { len: usize, width: u32, height: u32 },
+    MipCount { width: u16, height: u16, mipmaps: u8 },
+    LodRange { min_lod: f32, max_lod: f32 },
+    LodBias(f32),
+    // A palette format was set for a format without a palette.
+    PaletteFormat(TextureFormats, PaletteFormats),
+    // The alpha setting contradicts the format or the pixels, see `BTI::check_alpha`.
+    AlphaSetting(String),
+    // C14X2, which can't be encoded.
+    UnsupportedFormat(TextureFormats),
+    // The pixels have more colors than the C4/C8 palette can hold.
+    TooManyColors(TextureFormats),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Size { width, height } =>
+                write!(f, "{}x{} is outside of 1x1 to {}x{}", width, height, MAXSIZE, MAXSIZE),
+            BuildError::PixelCount { len, width, height } =>
+                write!(f, "{} bytes are not {}x{} RGBA pixels", len, width, height),
+            BuildError::MipCount { width, height, mipmaps } =>
+                write!(f, "{}x{} textures can have 1 to {} mip levels, not {}", width, height,
+                maxmipmaps(*width, *height), mipmaps),
+            BuildError::LodRange { min_lod, max_lod } =>
+                write!(f, "LOD range {}..{} has to be ascending and within 0..10", min_lod, max_lod),
+            BuildError::LodBias(lod_bias) => write!(f, "LOD bias {} is outside of -4..3.99", lod_bias),
+            BuildError::PaletteFormat(format, paletteformat) =>
+                write!(f, "{:?} textures have no palette, {:?} is only valid for C4, C8 and C14X2",
+                format, paletteformat),
+            BuildError::AlphaSetting(e) => write!(f, "{}", e),
+            BuildError::UnsupportedFormat(format) => write!(f, "{:?} textures can't be encoded", format),
+            BuildError::TooManyColors(format) => write!(f, "too many colors for {:?}", format),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BuildError {}
+
+// Builds a consistent BTI from RGBA pixels. Everything not set is picked like `BTI::from`
+// does: the format from the pixels, linear filtering and a single level.
+#[derive(Debug, Clone)]
+pub struct BtiBuilder {
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+    format: Option<TextureFormats>,
+    paletteformat: Option<PaletteFormats>,
+    wraps: WrapNodes,
+    wrapt: WrapNodes,
+    minfilter: FilterMode,
+    magfilter: FilterMode,
+    mipmaps: u8,
+    alphasetting: Option<u8>,
+    lod: Option<(f32, f32)>,
+    lod_bias: f32,
+    edge_lod: bool,
+    bias_clamp: bool,
+    max_aniso: Anisotropy,
+}
+
+impl BtiBuilder {
+    // RGBA8 pixels row by row, the length is checked by `build`.
+    pub fn from_rgba(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        Self {
+            pixels,
+            width,
+            height,
+            format: None,
+            paletteformat: None,
+            wraps: WrapNodes::ClampToEdge,
+            wrapt: WrapNodes::ClampToEdge,
+            minfilter: FilterMode::Linear,
+            magfilter: FilterMode::Linear,
+            mipmaps: 1,
+            alphasetting: None,
+            lod: None,
+            lod_bias: 0.0,
+            edge_lod: false,
+            bias_clamp: false,
+            max_aniso: Anisotropy::One,
+        }
+    }
+
+    #[cfg(feature = "image")]
+    pub fn from_image(img: image::RgbaImage) -> Self {
+        let (width, height) = img.dimensions();
+        Self::from_rgba(width, height, img.into_raw())
+    }
+
+    #[cfg(feature = "image")]
+    pub fn from_dynamic(img: &image::DynamicImage) -> Self {
+        Self::from_image(img.to_rgba8())
+    }
+
+    pub fn format(mut self, format: TextureFormats) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    // Only valid for the palette formats C4, C8 and C14X2.
+    pub fn paletteformat(mut self, paletteformat: PaletteFormats) -> Self {
+        self.paletteformat = Some(paletteformat);
+        self
+    }
+
+    pub fn wrap(self, wraps: WrapNodes, wrapt: WrapNodes) -> Self {
+        self.wraps(wraps).wrapt(wrapt)
+    }
+
+    pub fn wraps(mut self, wraps: WrapNodes) -> Self {
+        self.wraps = wraps;
+        self
+    }
+
+    pub fn wrapt(mut self, wrapt: WrapNodes) -> Self {
+        self.wrapt = wrapt;
+        self
+    }
+
+    pub fn filter(self, minfilter: FilterMode, magfilter: FilterMode) -> Self {
+        self.minfilter(minfilter).magfilter(magfilter)
+    }
+
+    pub fn minfilter(mut self, minfilter: FilterMode) -> Self {
+        self.minfilter = minfilter;
+        self
+    }
+
+    pub fn magfilter(mut self, magfilter: FilterMode) -> Self {
+        self.magfilter = magfilter;
+        self
+    }
+
+    // Number of levels including the full size one.
+    pub fn mipmaps(mut self, mipmaps: u8) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    // Overrides the alpha setting detected from the pixels, `build` fails if it contradicts them.
+    pub fn alphasetting(mut self, alphasetting: u8) -> Self {
+        self.alphasetting = Some(alphasetting);
+        self
+    }
+
+    // LOD range the hardware samples, 0 to 10. Without it every level is used.
+    pub fn lod(mut self, min_lod: f32, max_lod: f32) -> Self {
+        self.lod = Some((min_lod, max_lod));
+        self
+    }
+
+    // -4 to 3.99.
+    pub fn lod_bias(mut self, lod_bias: f32) -> Self {
+        self.lod_bias = lod_bias;
+        self
+    }
+
+    pub fn edge_lod(mut self, edge_lod: bool) -> Self {
+        self.edge_lod = edge_lod;
+        self
+    }
+
+    pub fn bias_clamp(mut self, bias_clamp: bool) -> Self {
+        self.bias_clamp = bias_clamp;
+        self
+    }
+
+    pub fn max_aniso(mut self, max_aniso: Anisotropy) -> Self {
+        self.max_aniso = max_aniso;
+        self
+    }
+
+    // Checks the settings against each other and the pixels, C4/C8 palettes are
+    // generated here.
+    pub fn build(self) -> Result<BTI, BuildError> {
+        if self.width == 0 || self.height == 0 || self.width > MAXSIZE || self.height > MAXSIZE {
+            return Err(BuildError::Size { width: self.width, height: self.height });
+        }
+        if self.pixels.len() != self.width as usize * self.height as usize * 4 {
+            return Err(BuildError::PixelCount { len: self.pixels.len(), width: self.width, height: self.height });
+        }
+        let (width, height) = (self.width as u16, self.height as u16);
+        if self.mipmaps == 0 || self.mipmaps > maxmipmaps(width, height) {
+            return Err(BuildError::MipCount { width, height, mipmaps: self.mipmaps });
+        }
+        let (min_lod, max_lod) = self.lod.unwrap_or((0.0, (self.mipmaps - 1) as f32));
+        if !(0.0..=10.0).contains(&min_lod) || !(min_lod..=10.0).contains(&max_lod) {
+            return Err(BuildError::LodRange { min_lod, max_lod });
+        }
+        if !(-4.0..=3.99).contains(&self.lod_bias) {
+            return Err(BuildError::LodBias(self.lod_bias));
+        }
+        let mut res = BTI {
+            width,
+            height,
+            wraps: self.wraps,
+            wrapt: self.wrapt,
+            minfilter: self.minfilter,
+            magfilter: self.magfilter,
+            mipmapcount: self.mipmaps,
+            mipmaps_enabled: self.mipmaps > 1,
+            min_lod,
+            max_lod,
+            lod_bias: self.lod_bias,
+            edge_lod: self.edge_lod,
+            bias_clamp: self.bias_clamp,
+            max_aniso: self.max_aniso,
+            rgbaimagedata: self.pixels,
+            ..Default::default()
+        };
+        decectandsetsittingformat(&mut res);
+        if let Some(format) = self.format {
+            res.format = format;
+        }
+        let indexed = matches!(res.format, TextureFormats::C4 | TextureFormats::C8 | TextureFormats::C14X2);
+        match self.paletteformat {
+            Some(paletteformat) if !indexed => {
+                return Err(BuildError::PaletteFormat(res.format, paletteformat));
+            },
+            Some(paletteformat) => res.paletteformat = paletteformat,
+            None => ()
+        }
+        match self.alphasetting {
+            Some(alphasetting) => {
+                res.alphasetting = alphasetting;
+                res.check_alpha().map_err(BuildError::AlphaSetting)?;
+            },
+            None => res.alphasetting = alphasetting(res.format, res.paletteformat, &res.rgbaimagedata) as u8
+        }
+        if res.format == TextureFormats::C14X2 {
+            return Err(BuildError::UnsupportedFormat(res.format));
+        }
+        if matches!(res.format, TextureFormats::C4 | TextureFormats::C8) && !res.generate_palette() {
+            return Err(BuildError::TooManyColors(res.format));
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    // 8x8 gray pixels using `colors` different values.
+    fn gray(colors: u32) -> Vec<u8> {
+        (0..64).flat_map(|i| {
+            let v = (i % colors * 15) as u8;
+            [v, v, v, 0xFF]
+        }).collect()
+    }
+
+    #[test]
+    fn rejects_bad_sizes() {
+        assert_eq!(BtiBuilder::from_rgba(0, 8, vec![]).build().unwrap_err(),
+            BuildError::Size { width: 0, height: 8 });
+        assert_eq!(BtiBuilder::from_rgba(2048, 1, vec![0; 2048 * 4]).build().unwrap_err(),
+            BuildError::Size { width: 2048, height: 1 });
+        assert_eq!(BtiBuilder::from_rgba(8, 9, gray(1)).build().unwrap_err(),
+            BuildError::PixelCount { len: 256, width: 8, height: 9 });
+        assert_eq!(BtiBuilder::from_rgba(8, 8, gray(1)).mipmaps(5).build().unwrap_err(),
+            BuildError::MipCount { width: 8, height: 8, mipmaps: 5 });
+        let bti = BtiBuilder::from_rgba(8, 8, gray(1)).mipmaps(4).build().unwrap();
+        assert_eq!((bti.width, bti.height, bti.mipmapcount, bti.max_lod), (8, 8, 4, 3.0));
+    }
+
+    #[test]
+    fn rejects_bad_palettes() {
+        let build = |format, colors| BtiBuilder::from_rgba(8, 8, gray(colors)).format(format)
+        .paletteformat(PaletteFormats::IA8).build();
+        assert_eq!(build(TextureFormats::CMPR, 2).unwrap_err(),
+            BuildError::PaletteFormat(TextureFormats::CMPR, PaletteFormats::IA8));
+        assert_eq!(build(TextureFormats::C4, 17).unwrap_err(), BuildError::TooManyColors(TextureFormats::C4));
+        assert_eq!(build(TextureFormats::C14X2, 2).unwrap_err(), BuildError::UnsupportedFormat(TextureFormats::C14X2));
+        let bti = build(TextureFormats::C4, 16).unwrap();
+        assert_eq!((bti.paletteformat, bti.palettecount), (PaletteFormats::IA8, 16));
+        assert_eq!(build(TextureFormats::C8, 17).unwrap().palettecount, 17);
+    }
+}
@@ -0,0 +1,236 @@
+use std::io::{Cursor, Read, Write};
+use image::error::{DecodingError, EncodingError, ImageFormatHint, ParameterError, ParameterErrorKind,
+    UnsupportedError, UnsupportedErrorKind};
+use image::{ColorType, ExtendedColorType, ImageDecoder, ImageDecoderRect, ImageEncoder, ImageError,
+    ImageResult, Progress};
+use crate::prelude::{BTI, BtiBuilder, BtiView, FilterMode, MAXSIZE, PaletteFormats, TextureFormats, WrapNodes,
+    maxmipmaps};
+
+fn formathint() -> ImageFormatHint {
+    ImageFormatHint::Name("BTI".to_string())
+}
+
+fn decodingerror(message: String) -> ImageError {
+    ImageError::Decoding(DecodingError::new(formathint(), message))
+}
+
+fn unsupported(message: String) -> ImageError {
+    ImageError::Unsupported(UnsupportedError::from_format_and_kind(formathint(),
+        UnsupportedErrorKind::GenericFeature(message)))
+}
+
+// Intensity formats and IA8 palettes only carry gray values, they are handed out as
+// Luma8/LumaA8 instead of expanding them to RGBA.
+pub fn colortype(format: TextureFormats, paletteformat: PaletteFormats) -> ColorType {
+    match format {
+        TextureFormats::I4 | TextureFormats::I8 => ColorType::L8,
+        TextureFormats::IA4 | TextureFormats::IA8 => ColorType::La8,
+        TextureFormats::C4 | TextureFormats::C8 if paletteformat == PaletteFormats::IA8 => ColorType::La8,
+        _ => ColorType::Rgba8
+    }
+}
+
+// Drops the channels RGBA pixels don't need for `color`.
+fn fromrgba(data: Vec<u8>, color: ColorType) -> Vec<u8> {
+    match color {
+        ColorType::L8 => data.chunks_exact(4).map(|x| x[0]).collect(),
+        ColorType::La8 => data.chunks_exact(4).flat_map(|x| [x[0], x[3]]).collect(),
+        _ => data
+    }
+}
+
+fn torgba(buf: &[u8], color: ColorType) -> ImageResult<Vec<u8>> {
+    Ok(match color {
+        ColorType::L8 => buf.iter().flat_map(|x| [*x, *x, *x, 0xFF]).collect(),
+        ColorType::La8 => buf.chunks_exact(2).flat_map(|x| [x[0], x[0], x[0], x[1]]).collect(),
+        ColorType::Rgb8 => buf.chunks_exact(3).flat_map(|x| [x[0], x[1], x[2], 0xFF]).collect(),
+        ColorType::Rgba8 => buf.to_vec(),
+        _ => return Err(unsupported(format!("{:?} pixels", color)))
+    })
+}
+
+// image::ImageDecoder for BTI files, e.g. `DynamicImage::from_decoder(BtiDecoder::new(file)?)`.
+// Decodes the first level unless another one is picked with `with_level`.
+pub struct BtiDecoder {
+    data: Vec<u8>,
+    header: BTI,
+    level: u8,
+    // Pixels of `level` in `color_type`, decoded on first use.
+    pixels: Option<Vec<u8>>,
+}
+
+impl BtiDecoder {
+    pub fn new<R: Read>(mut reader: R) -> ImageResult<Self> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        let header = BtiView::parse(&data).map_err(decodingerror)?.header;
+        if header.format == TextureFormats::C14X2 {
+            return Err(unsupported("C14X2 textures".to_string()));
+        }
+        Ok(Self { data, header, level: 0, pixels: None })
+    }
+
+    pub fn with_level(mut self, level: u8) -> ImageResult<Self> {
+        let levels = self.header.mipmapcount.max(1);
+        if level >= levels {
+            return Err(ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::Generic(
+                format!("Level {} requested, the texture has {}", level, levels)))));
+        }
+        self.level = level;
+        self.pixels = None;
+        Ok(self)
+    }
+
+    pub fn header(&self) -> &BTI {
+        &self.header
+    }
+
+    fn pixels(&mut self) -> ImageResult<&[u8]> {
+        if self.pixels.is_none() {
+            let view = BtiView::parse(&self.data).map_err(decodingerror)?;
+            let rgba = view.decode_level(self.level).map_err(decodingerror)?;
+            self.pixels = Some(fromrgba(rgba, self.color_type()));
+        }
+        Ok(self.pixels.as_deref().unwrap())
+    }
+}
+
+impl<'a> ImageDecoder<'a> for BtiDecoder {
+    type Reader = Cursor<Vec<u8>>;
+
+    fn dimensions(&self) -> (u32, u32) {
+        let (width, height) = crate::mipmap::levelsize(self.header.width, self.header.height, self.level);
+        (width.into(), height.into())
+    }
+
+    fn color_type(&self) -> ColorType {
+        colortype(self.header.format, self.header.paletteformat)
+    }
+
+    fn original_color_type(&self) -> ExtendedColorType {
+        match self.header.format {
+            TextureFormats::I4 => ExtendedColorType::L4,
+            TextureFormats::IA4 => ExtendedColorType::La4,
+            TextureFormats::RGB565 => ExtendedColorType::Rgb8,
+            format => colortype(format, self.header.paletteformat).into()
+        }
+    }
+
+    fn into_reader(mut self) -> ImageResult<Self::Reader> {
+        self.pixels()?;
+        Ok(Cursor::new(self.pixels.unwrap()))
+    }
+
+    fn read_image(mut self, buf: &mut [u8]) -> ImageResult<()> {
+        assert_eq!(buf.len() as u64, self.total_bytes());
+        buf.copy_from_slice(self.pixels()?);
+        Ok(())
+    }
+}
+
+impl<'a> ImageDecoderRect<'a> for BtiDecoder {
+    fn read_rect_with_progress<F: Fn(Progress)>(&mut self, x: u32, y: u32, width: u32, height: u32,
+        buf: &mut [u8], _progress_callback: F) -> ImageResult<()> {
+        // Progress can only be created by the image crate itself, so there is none to report.
+        let (fullwidth, fullheight) = self.dimensions();
+        if x as u64 + width as u64 > fullwidth as u64 || y as u64 + height as u64 > fullheight as u64 {
+            return Err(ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::DimensionMismatch)));
+        }
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let pixelsize = self.color_type().bytes_per_pixel() as usize;
+        let rowsize = width as usize * pixelsize;
+        let pixels = self.pixels()?;
+        for (row, dst) in buf.chunks_exact_mut(rowsize).take(height as usize).enumerate() {
+            let start = ((y as usize + row) * fullwidth as usize + x as usize) * pixelsize;
+            dst.copy_from_slice(&pixels[start..start + rowsize]);
+        }
+        Ok(())
+    }
+}
+
+// image::ImageEncoder writing a complete BTI file. Accepts L8, La8, Rgb8 and Rgba8 pixels,
+// without a format one is picked from the pixels like `BTI::from` does. The settings are
+// checked by BtiBuilder, its errors come back as encoding errors.
+pub struct BtiEncoder<W: Write> {
+    writer: W,
+    pub format: Option<TextureFormats>,
+    pub paletteformat: PaletteFormats,
+    pub wraps: WrapNodes,
+    pub wrapt: WrapNodes,
+    pub minfilter: FilterMode,
+    pub magfilter: FilterMode,
+    // Number of levels including the full size one, clamped to what the size allows.
+    pub mipmaps: u8,
+}
+
+impl<W: Write> BtiEncoder<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            format: None,
+            paletteformat: PaletteFormats::RGB5A3,
+            wraps: WrapNodes::ClampToEdge,
+            wrapt: WrapNodes::ClampToEdge,
+            minfilter: FilterMode::Linear,
+            magfilter: FilterMode::Linear,
+            mipmaps: 1,
+        }
+    }
+
+    pub fn with_format(mut self, format: TextureFormats) -> Self {
+        self.format = Some(format);
+        self
+    }
+}
+
+impl<W: Write> ImageEncoder for BtiEncoder<W> {
+    fn write_image(mut self, buf: &[u8], width: u32, height: u32, color_type: ColorType) -> ImageResult<()> {
+        let mut builder = BtiBuilder::from_rgba(width, height, torgba(buf, color_type)?)
+        .wrap(self.wraps, self.wrapt)
+        .filter(self.minfilter, self.magfilter)
+        .mipmaps(self.mipmaps.clamp(1, maxmipmaps(width.min(MAXSIZE) as u16, height.min(MAXSIZE) as u16)));
+        if let Some(format) = self.format {
+            builder = builder.format(format);
+            if matches!(format, TextureFormats::C4 | TextureFormats::C8 | TextureFormats::C14X2) {
+                builder = builder.paletteformat(self.paletteformat);
+            }
+        }
+        let bti = builder.build().map_err(|e| ImageError::Encoding(EncodingError::new(formathint(), e)))?;
+        let mut out = Cursor::new(vec![]);
+        bti.write_and_encode(&mut out);
+        self.writer.write_all(&out.into_inner())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An 8x8 RGBA32 texture where every pixel holds its own coordinates.
+    fn texture() -> Vec<u8> {
+        let pixels = (0..64u8).flat_map(|i| [i % 8, i / 8, 0, 0xFF]).collect::<Vec<_>>();
+        let mut res = vec![];
+        let mut encoder = BtiEncoder::new(&mut res);
+        encoder.format = Some(TextureFormats::RGBA32);
+        encoder.write_image(&pixels, 8, 8, ColorType::Rgba8).unwrap();
+        res
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn read_rect() {
+        let mut decoder = BtiDecoder::new(Cursor::new(texture())).unwrap();
+        let mut buf = [0u8; 2 * 3 * 4];
+        decoder.read_rect_with_progress(5, 4, 2, 3, &mut buf, |_| {}).unwrap();
+        assert_eq!(buf.chunks_exact(4).map(|x| (x[0], x[1])).collect::<Vec<_>>(),
+            [(5, 4), (6, 4), (5, 5), (6, 5), (5, 6), (6, 6)]);
+        // Empty rectangles are fine anywhere inside the texture, but not outside of it.
+        decoder.read_rect_with_progress(3, 3, 0, 5, &mut [], |_| {}).unwrap();
+        decoder.read_rect_with_progress(8, 0, 0, 8, &mut [], |_| {}).unwrap();
+        decoder.read_rect_with_progress(3, 3, 5, 0, &mut [], |_| {}).unwrap();
+        assert!(decoder.read_rect_with_progress(9, 0, 0, 1, &mut [], |_| {}).is_err());
+    }
+}
@@ -1,195 +1,234 @@
+#[cfg(feature = "std")]
 use std::io::*;
-use crate::prelude::{BTI, TextureFormats, NRange, Palette, PaletteFormats};
-use binrw::prelude::*;
+use alloc::{vec, vec::Vec};
+use crate::prelude::{BTI, TextureFormats, PaletteFormats, PixelLayout};
+use crate::simd;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
+// Reads the first image level once and decodes it from memory.
+#[cfg(feature = "std")]
 pub fn decode<R: Read + Seek>(reader: &mut R, bti: &BTI) -> Vec<u8> {
-    let (width, height) = (bti.width, bti.height);
-    let format = bti.format;
+    let mut src = vec![0u8; encodedsize(bti.format, bti.width, bti.height)];
+    reader.read_exact(&mut src).unwrap();
+    decodebytes(&src, bti)
+}
+
+// Decodes the first image level of `src` with the format and palette of `bti`.
+pub fn decodebytes(src: &[u8], bti: &BTI) -> Vec<u8> {
+    let mut dst = vec![0u8; bti.width as usize * bti.height as usize * 4];
+    match bti.format {
+        TextureFormats::C4 | TextureFormats::C8 => decode_indexed_into(bti.format, src,
+            bti.width, bti.height, &bti.imagepalette.palettedata, bti.paletteformat, &mut dst),
+        format => decode_into(format, src, bti.width, bti.height, &mut dst)
+    }
+    dst
+}
+
+// Block width, block height and bytes per block.
+pub fn blocksize(format: TextureFormats) -> (usize, usize, usize) {
+    match format {
+        TextureFormats::I4 | TextureFormats::C4 | TextureFormats::CMPR => (8, 8, 32),
+        TextureFormats::I8 | TextureFormats::IA4 | TextureFormats::C8 => (8, 4, 32),
+        TextureFormats::IA8 | TextureFormats::RGB565 | TextureFormats::RGB5A3
+        | TextureFormats::C14X2 => (4, 4, 32),
+        TextureFormats::RGBA32 => (4, 4, 64),
+    }
+}
+
+// Size of one image level, padded to whole blocks.
+pub fn encodedsize(format: TextureFormats, width: u16, height: u16) -> usize {
+    let (blockwidth, blockheight, stride) = blocksize(format);
+    (width as usize).div_ceil(blockwidth) * (height as usize).div_ceil(blockheight) * stride
+}
+
+// Decodes one image level of a format without palette into `dst`, which holds
+// width * height RGBA pixels.
+pub fn decode_into(format: TextureFormats, src: &[u8], width: u16, height: u16, dst: &mut [u8]) {
+    decode_into_layout(format, src, width, height, dst, PixelLayout::Rgba);
+}
+
+// Like decode_into, writing the pixels in `layout` order.
+pub fn decode_into_layout(format: TextureFormats, src: &[u8], width: u16, height: u16, dst: &mut [u8],
+    layout: PixelLayout) {
+    checksizes(format, src, width, height, dst);
     match format {
-        TextureFormats::I4 => decodei4(reader, width, height),
-        TextureFormats::I8 => decodei8(reader, width, height),
-        TextureFormats::IA4 => decodeia4(reader, width, height),
-        TextureFormats::IA8 => decodeia8(reader, width, height),
-        TextureFormats::RGB565 => decodergb565(reader, width, height),
-        TextureFormats::RGB5A3 => decodergb5a3(reader, width, height),
-        TextureFormats::RGBA32 => decodergba32(reader, width, height),
-        TextureFormats::C4 => decodec4(reader, width, height, &bti.imagepalette, 
-            bti.paletteformat),
-        TextureFormats::C8 => decodec8(reader, width, height, &bti.imagepalette,
-            bti.paletteformat),
-        TextureFormats::CMPR => decodecmpr(reader, width.into(), height.into()),
+        TextureFormats::I4 => forrows(format, src, width, height, dst, decodei4),
+        TextureFormats::I8 => forrows(format, src, width, height, dst, decodei8),
+        TextureFormats::IA4 => forrows(format, src, width, height, dst, decodeia4),
+        TextureFormats::IA8 => forrows(format, src, width, height, dst, decodeia8),
+        TextureFormats::RGB565 => forrows(format, src, width, height, dst, |src, width, height, dst| {
+            decodergb565(src, width, height, dst, layout)
+        }),
+        TextureFormats::RGB5A3 => forrows(format, src, width, height, dst, |src, width, height, dst| {
+            decodergb5a3(src, width, height, dst, layout)
+        }),
+        TextureFormats::RGBA32 => forrows(format, src, width, height, dst, |src, width, height, dst| {
+            decodergba32(src, width, height, dst, layout)
+        }),
+        TextureFormats::CMPR => forrows(format, src, width, height, dst, |src, width, height, dst| {
+            decodecmpr(src, width, height, dst, layout)
+        }),
+        TextureFormats::C4 | TextureFormats::C8 => panic!("{:?} needs a palette, see decode_indexed_into.",
+            format),
         _ => unimplemented!("Other Texture Formats are currently not supported.")
     }
 }
 
-pub fn decodei4<R: Read + Seek>(reader: &mut R, width: u16, height: u16) -> Vec<u8> {
-    let numblocksw = (width + 7) / 8;
-    let numblocksh = (height + 7) / 8;
-    let mut decodeddata = vec![0u8; (width * height * 4).into()];
-    for yblock in 0..numblocksh {
-        for xblock in 0..numblocksw {
-            for py in 0..8 {
-                for px in NRange::new(0, 8, 2) {
-                    if (xblock * 8 + px) >= width ||  (yblock * 8 + py) >= height {
-                        reader.seek(SeekFrom::Current(1)).unwrap();
-                        continue;
-                    }
-                    let data: u8 = reader.read_ne().unwrap();
-                    let t = (data & 0xF0) >> 4;
-                    let t2 = data & 0xF0;
-                    let destidx = (4 * width * ((yblock * 8) + py) + (xblock * 8) + px) as usize;
-                    decodeddata[destidx] = t * 0x11;
-                    decodeddata[destidx + 1] = t * 0x11;
-                    decodeddata[destidx + 2] = t * 0x11;
-                    decodeddata[destidx + 3] = t * 0x11;
+// Like decode_into for the palette formats, `palette` holds the raw two byte entries.
+pub fn decode_indexed_into(format: TextureFormats, src: &[u8], width: u16, height: u16,
+    palette: &[u8], paletteformat: PaletteFormats, dst: &mut [u8]) {
+    decode_indexed_into_layout(format, src, width, height, palette, paletteformat, dst, PixelLayout::Rgba);
+}
 
-                    decodeddata[destidx + 4] = t2 * 0x11;
-                    decodeddata[destidx + 5] = t2 * 0x11;
-                    decodeddata[destidx + 6] = t2 * 0x11;
-                    decodeddata[destidx + 7] = t2 * 0x11;
-                }
-            }
-        }
+#[allow(clippy::too_many_arguments)]
+pub fn decode_indexed_into_layout(format: TextureFormats, src: &[u8], width: u16, height: u16,
+    palette: &[u8], paletteformat: PaletteFormats, dst: &mut [u8], layout: PixelLayout) {
+    checksizes(format, src, width, height, dst);
+    let colors = expandpalette(palette, paletteformat, layout);
+    match format {
+        TextureFormats::C4 => forrows(format, src, width, height, dst, |src, width, height, dst| {
+            decodec4(src, width, height, &colors, dst)
+        }),
+        TextureFormats::C8 => forrows(format, src, width, height, dst, |src, width, height, dst| {
+            decodec8(src, width, height, &colors, dst)
+        }),
+        _ => panic!("{:?} is not a palette format.", format)
     }
-    decodeddata
 }
 
-pub fn decodei8<R: Read + Seek>(reader: &mut R, width: u16, height: u16) -> Vec<u8> {
-    let numblocksw = (width + 7) / 8;
-    let numblocksh = (height + 3) / 4;
-    let mut decodeddata = vec![0u8; (width * height * 4).into()];
-    for yblock in 0..numblocksh {
-        for xblock in 0..numblocksw {
-            for py in 0..4 {
-                for px in 0..8 {
-                    if (xblock * 8 + px) >= width || (yblock * 4 + py) >= height {
-                        reader.seek(SeekFrom::Current(1)).unwrap();
-                        continue;
-                    }
-                    let data: u8 = reader.read_ne().unwrap();
-                    let destidx = (width * ((yblock * 4) + py) + (xblock * 8) + px) as usize;
-                    decodeddata[destidx] = data;
-                    decodeddata[destidx + 1] = data;
-                    decodeddata[destidx + 2] = data;
-                    decodeddata[destidx + 3] = data;
-                }
-            }
-        }
+// Colors of all 256 possible indices, missing entries stay transparent black.
+pub fn expandpalette(palette: &[u8], format: PaletteFormats, layout: PixelLayout) -> [[u8; 4]; 256] {
+    let mut res = [[0u8; 4]; 256];
+    for (i, color) in res.iter_mut().enumerate().take(palette.len() / 2) {
+        unpackpixelfrompalette(i, color, 0, palette, format);
+        *color = layout.arrange(*color);
     }
-    decodeddata
+    res
 }
 
-pub fn decodeia4<R: Read + Seek>(reader: &mut R, width: u16, height: u16) -> Vec<u8> {
-    let numblocksw = (width + 7) / 8;
-    let numblocksh = (height + 3) / 4;
-    let mut decodeddata = vec![0u8; (width * height * 4).into()];
-    for yblock in 0..numblocksh {
-        for xblock in 0..numblocksw {
-            for py in 0..4 {
-                for px in 0..8 {
-                    if (xblock * 8 + px) >= width || (yblock * 4 + py) >= height {
-                        reader.seek(SeekFrom::Current(1)).unwrap();
-                        continue;
-                    }
-                    let value: u8 = reader.read_ne().unwrap();
-                    let alpha = (value & 0xF0) >> 4;
-                    let lum = value & 0x0F;
-                    let destidx = (4 * (width * ((yblock * 4) + py) + (xblock * 8) + px)) as usize;
-                    decodeddata[destidx] = lum * 0x11;
-                    decodeddata[destidx + 1] = lum * 0x11;
-                    decodeddata[destidx + 2] = lum * 0x11;
-                    decodeddata[destidx + 3] = alpha * 0x11;
-                }
-            }
-        }
+// Splits the image into rows of blocks which are decoded independently, in parallel
+// with the rayon feature. `decoderow` gets the encoded row, the image width, the
+// number of pixel rows and the pixels of those rows.
+fn forrows<F: Fn(&[u8], usize, usize, &mut [u8]) + Sync>(format: TextureFormats, src: &[u8],
+    width: u16, height: u16, dst: &mut [u8], decoderow: F) {
+    if width == 0 || height == 0 {
+        return;
     }
-    decodeddata
+    let (blockwidth, blockheight, stride) = blocksize(format);
+    let width = width as usize;
+    let srcrow = width.div_ceil(blockwidth) * stride;
+    let dstrow = width * blockheight * 4;
+    let src = &src[..srcrow * (height as usize).div_ceil(blockheight)];
+    let decode = |(src, dst): (&[u8], &mut [u8])| decoderow(src, width, dst.len() / (width * 4), dst);
+    #[cfg(feature = "rayon")]
+    src.par_chunks(srcrow).zip(dst.par_chunks_mut(dstrow)).for_each(decode);
+    #[cfg(not(feature = "rayon"))]
+    src.chunks(srcrow).zip(dst.chunks_mut(dstrow)).for_each(decode);
 }
 
-pub fn decodeia8<R: Read + Seek>(reader: &mut R, width: u16, height: u16) -> Vec<u8> {
-    let numblocksw = (width + 3) / 4;
-    let numblocksh = (height + 3) / 4;
-    let mut decodeddata = vec![0u8; (width * height * 4).into()];
-    for yblock in 0..numblocksh {
-        for xblock in 0..numblocksw {
-            for py in 0..4 {
-                for px in 0..4 {
-                    if (xblock * 4 + px) >= width || (yblock * 4 + py) >= height {
-                        reader.seek(SeekFrom::Current(2)).unwrap();
-                        continue;
+fn checksizes(format: TextureFormats, src: &[u8], width: u16, height: u16, dst: &[u8]) {
+    let needed = encodedsize(format, width, height);
+    assert!(src.len() >= needed, "{:?} data for {}x{} needs {} bytes, got {}.", format, width, height,
+        needed, src.len());
+    assert_eq!(dst.len(), width as usize * height as usize * 4, "Destination has to hold {}x{} pixels.",
+        width, height);
+}
+
+// Calls `f(destidx, i)` for every pixel inside the image, `i` being the index of the
+// pixel in block order including the padding of partial blocks.
+fn forpixels<F: FnMut(usize, usize)>(width: usize, height: usize, blockwidth: usize,
+    blockheight: usize, mut f: F) {
+    let mut i = 0;
+    for yblock in (0..height).step_by(blockheight) {
+        for xblock in (0..width).step_by(blockwidth) {
+            for y in yblock..yblock + blockheight {
+                for x in xblock..xblock + blockwidth {
+                    if x < width && y < height {
+                        f(4 * (width * y + x), i);
                     }
-                    let destidx = (4 * (width * ((yblock * 4) + py) + (xblock * 4) + px)) as usize;
-                    let byte0: u8 = reader.read_ne().unwrap();
-                    let byte1: u8 = reader.read_ne().unwrap();
-                    decodeddata[destidx + 3] = byte0;
-                    decodeddata[destidx + 2] = byte1;
-                    decodeddata[destidx + 1] = byte1;
-                    decodeddata[destidx] = byte1;
+                    i += 1;
                 }
             }
         }
     }
-    decodeddata
 }
 
-pub fn decodergb565<R: Read + Seek>(reader: &mut R, width: u16, height: u16) -> Vec<u8> {
-    let numblocksw = (width + 3) / 4;
-    let numblocksh = (height + 3) / 4;
-    let mut decodeddata = vec![0u8; (width * height * 4).into()];
-    for yblock in 0..numblocksh {
-        for xblock in 0..numblocksw {
-            for py in 0..4 {
-                for px in 0..4 {
-                    if (xblock * 4 + px) >= width || (yblock * 4 + py) >= height {
-                        reader.seek(SeekFrom::Current(2)).unwrap();
-                        continue;
-                    }
-                    let sourcepixel: u16 = reader.read_be().unwrap();
-                    let destidx = (4 * (width * ((yblock * 4) + py) + (xblock * 4) + px)) as usize;
-                    rgb565torgba8(sourcepixel, &mut decodeddata, destidx);
-                }
+pub fn decodei4(src: &[u8], width: usize, height: usize, dst: &mut [u8]) {
+    forpixels(width, height, 8, 8, |destidx, i| {
+        let value = (src[i / 2] >> (4 - (i % 2) * 4)) & 0x0F;
+        dst[destidx..destidx + 4].fill(value * 0x11);
+    });
+}
+
+pub fn decodei8(src: &[u8], width: usize, height: usize, dst: &mut [u8]) {
+    forpixels(width, height, 8, 4, |destidx, i| {
+        dst[destidx..destidx + 4].fill(src[i]);
+    });
+}
+
+pub fn decodeia4(src: &[u8], width: usize, height: usize, dst: &mut [u8]) {
+    forpixels(width, height, 8, 4, |destidx, i| {
+        let alpha = (src[i] & 0xF0) >> 4;
+        let lum = src[i] & 0x0F;
+        dst[destidx..destidx + 3].fill(lum * 0x11);
+        dst[destidx + 3] = alpha * 0x11;
+    });
+}
+
+pub fn decodeia8(src: &[u8], width: usize, height: usize, dst: &mut [u8]) {
+    forpixels(width, height, 4, 4, |destidx, i| {
+        dst[destidx + 3] = src[2 * i];
+        dst[destidx..destidx + 3].fill(src[2 * i + 1]);
+    });
+}
+
+// 4x4 blocks of 16 bit pixels, every row of a block is expanded at once.
+fn decode4x4<F: Fn(&[u8], &mut [u8], PixelLayout)>(src: &[u8], width: usize, height: usize,
+    dst: &mut [u8], layout: PixelLayout, expand: F) {
+    let blocksperrow = width.div_ceil(4);
+    let mut partial = [0u8; 16];
+    for (i, block) in src.chunks_exact(32).enumerate() {
+        let (x, y) = ((i % blocksperrow) * 4, (i / blocksperrow) * 4);
+        if y >= height {
+            break;
+        }
+        let size = 4.min(width - x) * 4;
+        for py in 0..4.min(height - y) {
+            let destidx = ((y + py) * width + x) * 4;
+            let row = &block[py * 8..py * 8 + 8];
+            if size == 16 {
+                expand(row, &mut dst[destidx..destidx + 16], layout);
+            } else {
+                expand(row, &mut partial, layout);
+                dst[destidx..destidx + size].copy_from_slice(&partial[..size]);
             }
         }
     }
-    decodeddata
 }
 
-pub fn rgb565torgba8(sourcepixel: u16, decodeddata: &mut Vec<u8>, destidx: usize) {
+pub fn decodergb565(src: &[u8], width: usize, height: usize, dst: &mut [u8], layout: PixelLayout) {
+    decode4x4(src, width, height, dst, layout, simd::rgb565x4);
+}
+
+pub fn rgb565torgba8(sourcepixel: u16, decodeddata: &mut [u8], destidx: usize) {
     let mut r = ((sourcepixel & 0xF800) >> 11) as u8;
     let mut g = ((sourcepixel & 0x7E0) >> 5) as u8;
     let mut b = (sourcepixel & 0x1F) as u8;
     r = (r << (8 - 5)) | (r >> (10 - 8));
     g = (g << (8 - 6)) | (g >> (12 - 8));
     b = (b << (8 - 5)) | (b >> (10 - 8));
-    decodeddata[destidx] = b;
+    decodeddata[destidx] = r;
     decodeddata[destidx + 1] = g;
-    decodeddata[destidx + 2] = r;
+    decodeddata[destidx + 2] = b;
     decodeddata[destidx + 3] = 0xFF;
 }
 
-pub fn decodergb5a3<R: Read + Seek>(reader: &mut R, width: u16, height: u16) -> Vec<u8> {
-    let numblocksw = (width + 3) / 4;
-    let numblocksh = (height + 3) / 4;
-    let mut decodeddata = vec![0u8; (width * height * 4).into()];
-    for yblock in 0..numblocksh {
-        for xblock in 0..numblocksw {
-            for py in 0..4 {
-                for px in 0..4 {
-                    if (xblock * 4 + px) >= width || (yblock * 4 + py) >= height {
-                        reader.seek(SeekFrom::Current(2)).unwrap();
-                        continue;
-                    }
-                    let sourcepixel: u16 = reader.read_be().unwrap();
-                    let destidx = (4 * (width * ((yblock * 4) + py) + (xblock * 4) + px)) as usize;
-                    rgb5a3torgba8(sourcepixel, &mut decodeddata, destidx);
-                }
-            }
-        }
-    }
-    decodeddata
+pub fn decodergb5a3(src: &[u8], width: usize, height: usize, dst: &mut [u8], layout: PixelLayout) {
+    decode4x4(src, width, height, dst, layout, simd::rgb5a3x4);
 }
 
-pub fn rgb5a3torgba8(sourcepixel: u16, decodeddata: &mut Vec<u8>, destidx: usize) {
+pub fn rgb5a3torgba8(sourcepixel: u16, decodeddata: &mut [u8], destidx: usize) {
     let mut r: u8;
     let mut g: u8;
     let mut b: u8;
@@ -214,91 +253,42 @@ pub fn rgb5a3torgba8(sourcepixel: u16, decodeddata: &mut Vec<u8>, destidx: usize
         g = (g << (8 - 4)) | g;
         b = (b << (8 - 4)) | b;
     }
-    decodeddata[destidx] = b;
+    decodeddata[destidx] = r;
     decodeddata[destidx + 1] = g;
-    decodeddata[destidx + 2] = r;
+    decodeddata[destidx + 2] = b;
     decodeddata[destidx + 3] = a;
 }
 
-pub fn decodergba32<R: Read + Seek>(reader: &mut R, width: u16, height: u16) -> Vec<u8> {
-    let numblocksw = (width + 3) / 4;
-    let numblocksh = (height + 3) / 4;
-    let mut decodeddata = vec![0u8; (width * height * 4).into()];
-    for yblock in 0..numblocksh {
-        for xblock in 0..numblocksw {
-            for py in 0..4 {
-                for px in 0..4 {
-                    if (xblock * 4 + px) >= width || (yblock * 4 + py) >= height {
-                        reader.seek(SeekFrom::Current(2)).unwrap();
-                        continue;
-                    }
-                    let destidx = (4 * (width * ((yblock * 4) + py) + (xblock * 4) + px)) as usize;
-                    decodeddata[destidx + 3] = reader.read_ne().unwrap();
-                    decodeddata[destidx + 2] = reader.read_ne().unwrap();
-                }
-            }
-            for py in 0..4 {
-                for px in 0..4 {
-                    if (xblock * 4 + px) >= width || (yblock * 4 + py) >= height {
-                        reader.seek(SeekFrom::Current(2)).unwrap();
-                        continue;
-                    }
-                    let destidx = (4 * (width * ((yblock * 4) + py) + (xblock * 4) + px)) as usize;
-                    decodeddata[destidx + 1] = reader.read_ne().unwrap();
-                    decodeddata[destidx] = reader.read_ne().unwrap();
-                }
-            }
-        }
-    }
-    decodeddata
+// Every 64 byte block holds the AR pairs of its 16 pixels followed by the GB pairs.
+pub fn decodergba32(src: &[u8], width: usize, height: usize, dst: &mut [u8], layout: PixelLayout) {
+    let (ri, bi) = match layout {
+        PixelLayout::Rgba => (0, 2),
+        PixelLayout::Bgra => (2, 0),
+    };
+    forpixels(width, height, 4, 4, |destidx, i| {
+        let ar = (i / 16) * 64 + (i % 16) * 2;
+        let gb = ar + 32;
+        dst[destidx + ri] = src[ar + 1];
+        dst[destidx + 1] = src[gb];
+        dst[destidx + bi] = src[gb + 1];
+        dst[destidx + 3] = src[ar];
+    });
 }
 
-pub fn decodec4<R: Read + Seek>(reader: &mut R, width: u16, height: u16, 
-    pallete: &Palette, format: PaletteFormats) -> Vec<u8> {
-    let numblocksw = (width + 7) / 8;
-    let numblocksh = (height + 7) / 8;
-    let mut decodeddata = vec![0u8; (width * height * 4).into()];
-    for yblock in 0..numblocksh {
-        for xblock in 0..numblocksw {
-            for py in 0..8 {
-                for px in NRange::new(0, 8, 2) {
-                    if (xblock * 8 + px) >= width || (yblock * 8 + py) >= height {
-                        reader.seek(SeekFrom::Current(1)).unwrap();
-                        continue;
-                    }
-                    let data: u8 = reader.read_ne().unwrap();
-                    let t = data & 0xF0;
-                    let t2 = data & 0x0F;
-                    let destidx = (width * ((yblock * 8) + py) + (xblock * 8) + px) as usize;
-                    decodeddata[destidx] = t >> 4;
-                    decodeddata[destidx + 1] = t2;
-                }
-            }
-        }
-    }
-    let mut finaldest = vec![0u8; decodeddata.len() / 2];
-    let pixelsize = match format {
-        PaletteFormats::IA8 => 2,
-        _ => 4
-    };
-    let mut destoff = 0;
-    for y in 0..height {
-        for x in 0..width {
-            let pallidx = decodeddata[(y * width + x) as usize];
-            unpackpixelfrompalette(pallidx.into(), &mut finaldest, destoff, 
-            &pallete.palettedata, format);
-            destoff += pixelsize;
-        }
-    }
-    finaldest
+// `colors` is the palette as returned by expandpalette.
+pub fn decodec4(src: &[u8], width: usize, height: usize, colors: &[[u8; 4]], dst: &mut [u8]) {
+    forpixels(width, height, 8, 8, |destidx, i| {
+        let pallidx = (src[i / 2] >> (4 - (i % 2) * 4)) & 0x0F;
+        dst[destidx..destidx + 4].copy_from_slice(&colors[pallidx as usize]);
+    });
 }
 
-pub fn unpackpixelfrompalette(pallidx: usize, finaldest: &mut Vec<u8>, destoff: usize, 
-    palettedata: &Vec<u8>, format: PaletteFormats) {
+pub fn unpackpixelfrompalette(pallidx: usize, finaldest: &mut [u8], destoff: usize,
+    palettedata: &[u8], format: PaletteFormats) {
         match format {
             PaletteFormats::IA8 => {
-                finaldest[0] = palettedata[2 * pallidx + 1];
-                finaldest[1] = palettedata[2 * pallidx];
+                finaldest[destoff..destoff + 3].fill(palettedata[2 * pallidx + 1]);
+                finaldest[destoff + 3] = palettedata[2 * pallidx];
             },
             PaletteFormats::RGB565 => {
                 let mut sourcepixel = (palettedata[2 * pallidx] as u16) << 8;
@@ -313,110 +303,49 @@ pub fn unpackpixelfrompalette(pallidx: usize, finaldest: &mut Vec<u8>, destoff:
         }
 }
 
-pub fn decodec8<R: Read + Seek>(reader: &mut R, width: u16 , height: u16,
-    pallete: &Palette, format: PaletteFormats) -> Vec<u8> {
-        let numblocksw = (width + 7) / 8;
-        let numblocksh = (height + 3) / 4;
-        let mut decodeddata = vec![0u8; (width * height * 8).into()];
-        for yblock in 0..numblocksh {
-            for xblock in 0..numblocksw {
-                for py in 0..4 {
-                    for px in 0..8 {
-                        if (xblock * 8 + px) >= width || (yblock * 4 + py) >= height {
-                            reader.seek(SeekFrom::Current(1)).unwrap();
-                            continue;
-                        }
-                        let destidx = (width * ((yblock * 4) + py) + (xblock * 8) + px) as usize;
-                        let data: u8 = reader.read_ne().unwrap();
-                        decodeddata[destidx] = data;
-                    }
-                }
-            }
-        }
-        let mut finaldest = vec![0u8; decodeddata.len() / 2];
-        let pixelsize = match format {
-            PaletteFormats::IA8 => 4,
-            _ => 2
-        };
-        let mut destoff = 0;
-        for y in 0..height {
-            for x in 0..width {
-                let pallidx = decodeddata[(y * width + x) as usize];
-                unpackpixelfrompalette(pallidx.into(), &mut finaldest, destoff, 
-                &pallete.palettedata, format);
-                destoff += pixelsize;
-            }
-        }
-        finaldest
+pub fn decodec8(src: &[u8], width: usize, height: usize, colors: &[[u8; 4]], dst: &mut [u8]) {
+    forpixels(width, height, 8, 4, |destidx, i| {
+        dst[destidx..destidx + 4].copy_from_slice(&colors[src[i] as usize]);
+    });
 }
 
-pub fn decodecmpr<R: Read + Seek>(reader: &mut R, width: u32, height: u32) -> Vec<u8> {
-    let numblocksw = (width + 7) / 8;
-    let numblocksh = (height + 7) / 8;
-    let mut decodeddata = vec![0u8; (width * height * 4) as usize];
-    for yblock in 0..numblocksh {
-        for xblock in 0..numblocksw {
+// 8x8 blocks made of four 4x4 sub-blocks of 8 bytes each.
+pub fn decodecmpr(src: &[u8], width: usize, height: usize, dst: &mut [u8], layout: PixelLayout) {
+    let mut chunks = src.chunks_exact(8);
+    for yblock in (0..height).step_by(8) {
+        for xblock in (0..width).step_by(8) {
             for ysubblock in 0..2 {
                 for xsubblock in 0..2 {
-                    let mut num = width - (xsubblock * 4 + xblock * 8);
-                    let subblockwidth = 0.max(4.min(num));
-                    num = height - (ysubblock * 4 + yblock * 8);
-                    let subblockheight = 0.max(4.min(num));
-                    let subblock = decodecmprsubblock(reader);
-                    for py in 0..subblockheight {
-                        let destx = xblock * 8 + xsubblock * 4;
-                        let desty = yblock * 8 + ysubblock * 4 + py;
-                        if destx >= width || desty >= height {
-                            continue;
-                        }
-                        let destoff = ((desty * width + destx) * 4) as usize;
-                        let size = (subblockwidth * 4) as usize;
-                        let idx = (py * 4 * 4) as usize;
-                        for i in 0..size {
-                            decodeddata[destoff + i] = subblock[idx + i];
+                    let subblock = decodecmprsubblock(chunks.next().unwrap(), layout);
+                    let destx = xblock + xsubblock * 4;
+                    if destx >= width {
+                        continue;
+                    }
+                    let size = 4.min(width - destx) * 4;
+                    for py in 0..4 {
+                        let desty = yblock + ysubblock * 4 + py;
+                        if desty >= height {
+                            break;
                         }
+                        let destoff = (desty * width + destx) * 4;
+                        let idx = py * 4 * 4;
+                        dst[destoff..destoff + size].copy_from_slice(&subblock[idx..idx + size]);
                     }
                 }
             }
         }
     }
-    decodeddata
 }
 
-pub fn decodecmprsubblock<R: Read + Seek>(reader: &mut R) -> Vec<u8> {
-    let mut decodeddata = vec![0u8; 4 * 4 * 4];
-    let color1: u16 = reader.read_be().unwrap();
-    let color2: u16 = reader.read_be().unwrap();
-    let bits: u32 = reader.read_be().unwrap();
-    let mut colortable = vec![vec![0u8; 4]; 4];
-    rgb565torgba8(color1, &mut colortable[0], 0);
-    rgb565torgba8(color2, &mut colortable[1], 0);
-    if color1 > color2 {
-        for i in 0..3 {
-            colortable[2][i] = ((2 * colortable[0][i] as u16 + colortable[1][i] as u16) / 3) as u8;
-            colortable[3][i] = ((colortable[0][i] as u16 + 2 * colortable[1][i] as u16) / 3) as u8;
-        }
-        colortable[2][3] = 0xFF;
-        colortable[3][3] = 0xFF;
-    } else {
-        for i in 0..3 {
-            colortable[2][i] = ((colortable[0][i] as u16 + colortable[1][i] as u16) / 2) as u8;
-            colortable[3][i] = ((colortable[0][i] as u16 + colortable[1][i] as u16) / 3) as u8;
-        }
-        colortable[2][3] = 0xFF;
-        colortable[3][3] = 0xFF;
-    }
-    for iy in 0..4 {
-        for ix in 0..4 {
-            let i = iy * 4 + ix;
-            let bitoff = (15 - i) * 2;
-            let di = i * 4;
-            let si = ((bits >> bitoff) & 0x3) as usize;
-            decodeddata[di] = colortable[si][0];
-            decodeddata[di + 1] = colortable[si][1];
-            decodeddata[di + 2] = colortable[si][2];
-            decodeddata[di + 3] = colortable[si][3];
-        }
+pub fn decodecmprsubblock(src: &[u8], layout: PixelLayout) -> [u8; 64] {
+    let mut decodeddata = [0u8; 4 * 4 * 4];
+    let color1 = u16::from_be_bytes([src[0], src[1]]);
+    let color2 = u16::from_be_bytes([src[2], src[3]]);
+    let bits = u32::from_be_bytes([src[4], src[5], src[6], src[7]]);
+    let colortable = simd::cmprpalette(color1, color2).map(|color| layout.arrange(color));
+    for (i, pixel) in decodeddata.chunks_exact_mut(4).enumerate() {
+        let si = ((bits >> ((15 - i) * 2)) & 0x3) as usize;
+        pixel.copy_from_slice(&colortable[si]);
     }
     decodeddata
-}
\ No newline at end of file
+}
@@ -0,0 +1,191 @@
+use image::{Rgba, RgbaImage};
+use crate::prelude::BTI;
+use crate::quality::{self, QualityReport};
+
+const OUTLINECOLOR: Rgba<u8> = Rgba([0xFF, 0x00, 0xFF, 0xFF]);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    pub field: &'static str,
+    pub left: String,
+    pub right: String,
+}
+
+// Bounding box of a connected area of changed pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BtiDiff {
+    pub header: Vec<FieldDiff>,
+    // Empty if the dimensions differ, the pixels can't be compared then.
+    pub regions: Vec<Region>,
+    pub changedpixels: usize,
+    pub stats: Option<QualityReport>,
+}
+
+impl BtiDiff {
+    pub fn is_empty(&self) -> bool {
+        self.header.is_empty() && self.changedpixels == 0 && self.stats.is_some()
+    }
+}
+
+macro_rules! diff_fields {
+    ($res:expr, $left:expr, $right:expr, [$($field:ident),+]) => {
+        $(
+            if $left.$field != $right.$field {
+                $res.push(FieldDiff {
+                    field: stringify!($field),
+                    left: format!("{:?}", $left.$field),
+                    right: format!("{:?}", $right.$field),
+                });
+            }
+        )+
+    };
+}
+
+pub fn diffheaders(left: &BTI, right: &BTI) -> Vec<FieldDiff> {
+    let mut res = vec![];
+    diff_fields!(res, left, right, [format, alphasetting, width, height, wraps, wrapt,
+        palettesenabled, paletteformat, palettecount, palettedataoffset, mipmaps_enabled, edge_lod, bias_clamp,
+        max_aniso, minfilter, magfilter, min_lod, max_lod, mipmapcount, unknown3, lod_bias, imagedataoffset]);
+    if left.imagepalette.palettedata != right.imagepalette.palettedata {
+        res.push(FieldDiff {
+            field: "imagepalette",
+            left: format!("{} entries", left.imagepalette.count()),
+            right: format!("{} entries", right.imagepalette.count()),
+        });
+    }
+    res
+}
+
+fn changedmask(left: &[u8], right: &[u8]) -> Vec<bool> {
+    left.chunks_exact(4).zip(right.chunks_exact(4)).map(|(a, b)| a != b).collect()
+}
+
+// Groups changed pixels into 8-connected regions.
+fn findregions(mask: &[bool], width: u32, height: u32) -> Vec<Region> {
+    let (w, h) = (width as usize, height as usize);
+    let mut visited = vec![false; mask.len()];
+    let mut res = vec![];
+    let mut stack = vec![];
+    for start in 0..mask.len() {
+        if !mask[start] || visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        stack.push(start);
+        let (mut minx, mut miny, mut maxx, mut maxy) = (w, h, 0, 0);
+        let mut pixels = 0;
+        while let Some(idx) = stack.pop() {
+            let (x, y) = (idx % w, idx / w);
+            minx = minx.min(x);
+            miny = miny.min(y);
+            maxx = maxx.max(x);
+            maxy = maxy.max(y);
+            pixels += 1;
+            for ny in y.saturating_sub(1)..(y + 2).min(h) {
+                for nx in x.saturating_sub(1)..(x + 2).min(w) {
+                    let nidx = ny * w + nx;
+                    if mask[nidx] && !visited[nidx] {
+                        visited[nidx] = true;
+                        stack.push(nidx);
+                    }
+                }
+            }
+        }
+        res.push(Region {
+            x: minx as u32,
+            y: miny as u32,
+            width: (maxx - minx + 1) as u32,
+            height: (maxy - miny + 1) as u32,
+            pixels,
+        });
+    }
+    res
+}
+
+pub fn diff(left: &BTI, right: &BTI) -> BtiDiff {
+    let mut res = BtiDiff {
+        header: diffheaders(left, right),
+        ..Default::default()
+    };
+    if left.width != right.width || left.height != right.height {
+        return res;
+    }
+    let (width, height) = (left.width as u32, left.height as u32);
+    let mask = changedmask(&left.rgbaimagedata, &right.rgbaimagedata);
+    res.changedpixels = mask.iter().filter(|x| **x).count();
+    res.regions = findregions(&mask, width, height);
+    res.stats = Some(quality::compare(&left.rgbaimagedata, &right.rgbaimagedata, width, height));
+    res
+}
+
+// Error heatmap of the two textures with the changed regions outlined, None if
+// the dimensions differ.
+pub fn diffimage(left: &BTI, right: &BTI) -> Option<RgbaImage> {
+    if left.width != right.width || left.height != right.height {
+        return None;
+    }
+    let (width, height) = (left.width as u32, left.height as u32);
+    let mut res = quality::heatmap(&left.rgbaimagedata, &right.rgbaimagedata, width, height);
+    let mask = changedmask(&left.rgbaimagedata, &right.rgbaimagedata);
+    for region in findregions(&mask, width, height) {
+        let (x2, y2) = (region.x + region.width - 1, region.y + region.height - 1);
+        for x in region.x..=x2 {
+            res.put_pixel(x, region.y, OUTLINECOLOR);
+            res.put_pixel(x, y2, OUTLINECOLOR);
+        }
+        for y in region.y..=y2 {
+            res.put_pixel(region.x, y, OUTLINECOLOR);
+            res.put_pixel(x2, y, OUTLINECOLOR);
+        }
+    }
+    Some(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::WrapNodes;
+
+    #[test]
+    fn two_regions() {
+        let left = BTI::from(RgbaImage::from_pixel(16, 16, Rgba([0x40, 0x80, 0xC0, 0xFF])));
+        let mut right = left.clone();
+        right.wraps = WrapNodes::Repeat;
+        // A 2x2 square and a diagonal line, 8-connected so it is one region.
+        for (x, y) in [(1, 1), (2, 1), (1, 2), (2, 2), (10, 5), (11, 6), (12, 7)] {
+            let idx = (y * 16 + x) * 4;
+            right.rgbaimagedata[idx..idx + 4].copy_from_slice(&[0xFF, 0, 0, 0xFF]);
+        }
+        let res = diff(&left, &right);
+        assert_eq!(res.header, vec![FieldDiff { field: "wraps", left: "ClampToEdge".into(), right: "Repeat".into() }]);
+        assert_eq!(res.changedpixels, 7);
+        assert_eq!(res.regions, vec![
+            Region { x: 1, y: 1, width: 2, height: 2, pixels: 4 },
+            Region { x: 10, y: 5, width: 3, height: 3, pixels: 3 },
+        ]);
+        assert!(!res.is_empty());
+        let image = diffimage(&left, &right).unwrap();
+        assert_eq!(*image.get_pixel(12, 5), OUTLINECOLOR);
+        assert_ne!(*image.get_pixel(11, 6), OUTLINECOLOR);
+        assert!(diff(&left, &left).is_empty());
+    }
+
+    #[test]
+    fn different_sizes() {
+        let left = BTI::from(RgbaImage::new(8, 8));
+        let right = BTI::from(RgbaImage::new(8, 4));
+        let res = diff(&left, &right);
+        assert_eq!(res.header.iter().map(|x| x.field).collect::<Vec<_>>(), ["height"]);
+        assert!(res.regions.is_empty() && res.stats.is_none() && !res.is_empty());
+        assert!(diffimage(&left, &right).is_none());
+    }
+}
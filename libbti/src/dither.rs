@@ -0,0 +1,235 @@
+use alloc::{vec, vec::Vec};
+use crate::prelude::{Dither, Palette, PaletteFormats, TextureFormats, dataformat, intensity};
+
+// 8x8 Bayer matrix, thresholds 0 to 63.
+const BAYER: [[i32; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+// Error diffusion kernels as (dx, dy, weight) and the sum the weights are divided by.
+const FLOYDSTEINBERG: ([(isize, usize, i32); 4], i32) = ([(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)], 16);
+// Atkinson only passes on 6/8 of the error, which keeps more contrast.
+const ATKINSON: ([(isize, usize, i32); 6], i32) = ([(1, 0, 1), (2, 0, 1), (-1, 1, 1), (0, 1, 1), (1, 1, 1),
+    (0, 2, 1)], 8);
+
+// Rounds an 8 bit value to `bits` like the encoders do and expands it back the way the
+// decoders do, by repeating the bits, e.g. 5 bit 3 becomes 24 and not 25.
+fn snapchannel(value: i32, bits: i32) -> i32 {
+    let max = (1 << bits) - 1;
+    let scaled = (value.clamp(0, 255) * max + 127) / 255;
+    let mut res = 0;
+    let mut shift = 8 - bits;
+    while shift > -bits {
+        res |= if shift >= 0 { scaled << shift } else { scaled >> -shift };
+        shift -= bits;
+    }
+    res
+}
+
+fn step(bits: i32) -> i32 {
+    match bits {
+        1..=7 => 255 / ((1 << bits) - 1),
+        _ => 0
+    }
+}
+
+// The values a format can store.
+enum Levels {
+    // Bits per color channel and for alpha, alpha isn't touched with 0 bits. Intensity
+    // formats store a single gray value.
+    Direct { color: [i32; 3], alpha: i32, intensity: bool },
+    // 5 bit colors for opaque pixels, 4 bit colors and 3 bit alpha for the others.
+    Rgb5a3,
+    // Palette::generate keeps the exact colors of the image, only pixels that got new colors
+    // from mip filtering or the other options can end up between two entries.
+    Palette(Vec<[u8; 4]>),
+}
+
+impl Levels {
+    // None if dithering can't help, i.e. for 8 bit and compressed formats.
+    fn new(format: TextureFormats, paletteformat: PaletteFormats, palette: &Palette) -> Option<Self> {
+        let data = dataformat(format)?;
+        if data.palette {
+            let colors = palette.colors(paletteformat);
+            return (!colors.is_empty()).then_some(Levels::Palette(colors));
+        }
+        if data.lossy || (data.colordepth == [8, 8, 8] && matches!(data.alphadepth, 0 | 8)) {
+            return None;
+        }
+        Some(match format {
+            TextureFormats::RGB5A3 => Levels::Rgb5a3,
+            _ => Levels::Direct { color: data.colordepth, alpha: data.alphadepth, intensity: !data.hascolor }
+        })
+    }
+
+    // The stored value closest to `pix`, picked the same way the encoders pick it.
+    fn snap(&self, pix: [i32; 4]) -> [i32; 4] {
+        let pix = pix.map(|x| x.clamp(0, 255));
+        match self {
+            Levels::Direct { color, alpha, intensity: gray } => {
+                let a = if *alpha == 0 { pix[3] } else { snapchannel(pix[3], *alpha) };
+                if *gray {
+                    let i = snapchannel(intensity(&pix.map(|x| x as u8)) as i32, color[0]);
+                    [i, i, i, a]
+                } else {
+                    [snapchannel(pix[0], color[0]), snapchannel(pix[1], color[1]), snapchannel(pix[2], color[2]), a]
+                }
+            },
+            Levels::Rgb5a3 => {
+                let a = snapchannel(pix[3], 3);
+                let bits = if a == 0xFF { 5 } else { 4 };
+                [snapchannel(pix[0], bits), snapchannel(pix[1], bits), snapchannel(pix[2], bits), a]
+            },
+            Levels::Palette(colors) => {
+                let color = colors.iter().min_by_key(|color| {
+                    color.iter().zip(pix).map(|(a, b)| (*a as i32 - b) * (*a as i32 - b)).sum::<i32>()
+                }).unwrap();
+                color.map(|x| x as i32)
+            }
+        }
+    }
+
+    // Difference between what was wanted and what is stored, for intensity formats the
+    // intensity difference goes into every color channel.
+    fn error(&self, want: [i32; 4], got: [i32; 4]) -> [i32; 4] {
+        let res = [0, 1, 2, 3].map(|c| want[c] - got[c]);
+        match self {
+            Levels::Direct { intensity: true, .. } => {
+                let diff = intensity(&want.map(|x| x as u8)) as i32 - got[0];
+                [diff, diff, diff, res[3]]
+            },
+            _ => res
+        }
+    }
+
+    // Distance between neighbouring stored values, ordered dithering spreads values by
+    // up to half of it in either direction.
+    fn step(&self) -> [i32; 4] {
+        match self {
+            Levels::Direct { color, alpha, .. } => [step(color[0]), step(color[1]), step(color[2]), step(*alpha)],
+            Levels::Rgb5a3 => [step(4), step(4), step(4), step(3)],
+            Levels::Palette(colors) => [0, 1, 2, 3].map(|c| {
+                // Mean gap between the distinct values of the channel.
+                let mut values = colors.iter().map(|x| x[c] as i32).collect::<Vec<i32>>();
+                values.sort_unstable();
+                values.dedup();
+                match values.len() {
+                    1 => 0,
+                    n => (values[n - 1] - values[0]) / (n as i32 - 1)
+                }
+            }),
+        }
+    }
+}
+
+fn diffuse(data: &mut [u8], width: usize, height: usize, levels: &Levels, kernel: &[(isize, usize, i32)],
+    divisor: i32) {
+    // Weighted errors of the current and the next two rows.
+    let mut errors = vec![[0i32; 4]; width * 3];
+    for y in 0..height {
+        for x in 0..width {
+            let idx = (y * width + x) * 4;
+            let err = core::mem::take(&mut errors[(y % 3) * width + x]);
+            let want = [0, 1, 2, 3].map(|c| (data[idx + c] as i32 + err[c] / divisor).clamp(0, 255));
+            let got = levels.snap(want);
+            let error = levels.error(want, got);
+            for &(dx, dy, weight) in kernel {
+                let nx = x as isize + dx;
+                if nx < 0 || nx >= width as isize || y + dy >= height {
+                    continue;
+                }
+                let target = &mut errors[((y + dy) % 3) * width + nx as usize];
+                for c in 0..4 {
+                    target[c] += error[c] * weight;
+                }
+            }
+            for c in 0..4 {
+                data[idx + c] = got[c] as u8;
+            }
+        }
+    }
+}
+
+// Snaps the RGBA pixels of a whole level to the values `format` stores, spreading the
+// rounding error with `mode`. Running on the full level before it is split into blocks
+// lets the error cross block boundaries, the encoder then stores the pixels unchanged.
+pub fn dither(data: &mut [u8], width: usize, height: usize, format: TextureFormats, paletteformat: PaletteFormats,
+    palette: &Palette, mode: Dither) {
+    let Some(levels) = Levels::new(format, paletteformat, palette) else {
+        return;
+    };
+    match mode {
+        Dither::None => (),
+        Dither::Bayer => {
+            let step = levels.step();
+            for (i, pix) in data.chunks_exact_mut(4).enumerate() {
+                let threshold = BAYER[(i / width) % 8][(i % width) % 8];
+                let want = [0, 1, 2, 3].map(|c| pix[c] as i32 + (2 * threshold + 1 - 64) * step[c] / 128);
+                let got = levels.snap(want);
+                for c in 0..4 {
+                    pix[c] = got[c] as u8;
+                }
+            }
+        },
+        Dither::FloydSteinberg => diffuse(data, width, height, &levels, &FLOYDSTEINBERG.0, FLOYDSTEINBERG.1),
+        Dither::Atkinson => diffuse(data, width, height, &levels, &ATKINSON.0, ATKINSON.1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A slow RGB gradient, rounding alone would turn it into flat bands.
+    fn gradient() -> Vec<u8> {
+        (0..64 * 64).flat_map(|i| {
+            let (x, y) = (i % 64, i / 64);
+            [(96 + x / 4) as u8, (64 + y / 8) as u8, (160 + (x + y) / 16) as u8, 0xFF]
+        }).collect()
+    }
+
+    // Largest difference of the mean of any 8x8 block from the original.
+    fn blockerror(a: &[u8], b: &[u8]) -> f64 {
+        let mut res = 0f64;
+        for (bx, by, c) in (0..64).flat_map(|i| (0..3).map(move |c| (i % 8, i / 8, c))) {
+            let sum = |data: &[u8]| (0..64).map(|i| data[((by * 8 + i / 8) * 64 + bx * 8 + i % 8) * 4 + c] as f64)
+                .sum::<f64>();
+            res = res.max((sum(a) - sum(b)).abs() / 64.0);
+        }
+        res
+    }
+
+    #[test]
+    fn error_bound() {
+        let source = gradient();
+        let levels = Levels::new(TextureFormats::RGB565, PaletteFormats::IA8, &Palette::default()).unwrap();
+        let mut rounded = source.clone();
+        for pix in rounded.chunks_exact_mut(4) {
+            let got = levels.snap([0, 1, 2, 3].map(|c| pix[c] as i32));
+            pix.copy_from_slice(&got.map(|x| x as u8));
+        }
+        // Atkinson drops a quarter of the error, so its blocks drift further.
+        for (mode, bound) in [(Dither::Bayer, 1.5), (Dither::FloydSteinberg, 1.5), (Dither::Atkinson, 2.5)] {
+            let mut data = source.clone();
+            dither(&mut data, 64, 64, TextureFormats::RGB565, PaletteFormats::IA8, &Palette::default(), mode);
+            for (pix, orig) in data.chunks_exact(4).zip(source.chunks_exact(4)) {
+                // Only values RGB565 stores, at most a step and a bit from the original.
+                let stored = [0, 1, 2, 3].map(|c| pix[c] as i32);
+                assert_eq!(levels.snap(stored), stored);
+                assert!(pix.iter().zip(orig).all(|(a, b)| (*a as i32 - *b as i32).abs() <= 12), "{:?}", mode);
+            }
+            assert!(blockerror(&data, &source) < bound, "{:?} {}", mode, blockerror(&data, &source));
+        }
+        assert!(blockerror(&rounded, &source) >= 3.0);
+        let mut data = source.clone();
+        dither(&mut data, 64, 64, TextureFormats::RGBA32, PaletteFormats::IA8, &Palette::default(), Dither::Bayer);
+        assert_eq!(data, source);
+    }
+}
@@ -1,14 +1,17 @@
-use crate::prelude::{ImageDataFormat, Converter};
+use crate::prelude::{ImageDataFormat, Converter, TextureFormats};
 
 // ImageDataFormat impls
-const I4: ImageDataFormat = ImageDataFormat::new("I4", "I4", 4, 0, 8, 8, 32, false, false, false, false, 0, 0);
-const I8: ImageDataFormat = ImageDataFormat::new("I8", "I8", 8, 0, 8, 4, 32, false, false, false, false, 0, 0);
-const IA4: ImageDataFormat = ImageDataFormat::new("IA4", "IA4", 8, 4, 8, 4, 32, false, false, false, false, 0, 0);
-const IA8: ImageDataFormat = ImageDataFormat::new("IA8", "IA8", 16, 8, 4, 4, 32, false, false, false, false, 0, 0);
-const RGB565: ImageDataFormat = ImageDataFormat::new("RGB565", "RGB565", 16, 0, 4, 4, 32, true, false, false, false, 0, 0);
-const RGB5A3: ImageDataFormat = ImageDataFormat::new("RGB5A3", "RGB5A3", 16, 3, 4, 4, 32, true, false, false, false, 0, 0);
-const RGBA32: ImageDataFormat = ImageDataFormat::new("RGBA32", "RGBA32", 32, 8, 4, 4, 64, true, false, false, false, 0, 0);
-const CMPR: ImageDataFormat = ImageDataFormat::new("CMPR", "CMPR", 4, 1, 8, 8, 32, true, true, true, false, 0, 0);
+const I4: ImageDataFormat = ImageDataFormat::new("I4", "I4", 4, [4, 4, 4], 0, 8, 8, 32, false, false, false, false, 0, 0);
+const I8: ImageDataFormat = ImageDataFormat::new("I8", "I8", 8, [8, 8, 8], 0, 8, 4, 32, false, false, false, false, 0, 0);
+const IA4: ImageDataFormat = ImageDataFormat::new("IA4", "IA4", 8, [4, 4, 4], 4, 8, 4, 32, false, false, false, false, 0, 0);
+const IA8: ImageDataFormat = ImageDataFormat::new("IA8", "IA8", 16, [8, 8, 8], 8, 4, 4, 32, false, false, false, false, 0, 0);
+const RGB565: ImageDataFormat = ImageDataFormat::new("RGB565", "RGB565", 16, [5, 6, 5], 0, 4, 4, 32, true, false, false, false, 0, 0);
+// Only opaque RGB5A3 pixels have 5 bits per color channel, the others have 4.
+const RGB5A3: ImageDataFormat = ImageDataFormat::new("RGB5A3", "RGB5A3", 16, [5, 5, 5], 3, 4, 4, 32, true, false, false, false, 0, 0);
+const RGBA32: ImageDataFormat = ImageDataFormat::new("RGBA32", "RGBA32", 32, [8, 8, 8], 8, 4, 4, 64, true, false, false, false, 0, 0);
+const CMPR: ImageDataFormat = ImageDataFormat::new("CMPR", "CMPR", 4, [5, 6, 5], 1, 8, 8, 32, true, true, true, false, 0, 0);
+const C4: ImageDataFormat = ImageDataFormat::new("C4", "C4", 4, [0, 0, 0], 0, 8, 8, 32, true, false, false, true, 16, 16);
+const C8: ImageDataFormat = ImageDataFormat::new("C8", "C8", 8, [0, 0, 0], 0, 8, 4, 32, true, false, false, true, 256, 16);
 
 // Encoder declarations
 pub enum I4{}
@@ -19,65 +22,99 @@ pub enum RGB565{}
 pub enum RGB5A3{}
 pub enum RGBA32{}
 pub enum CMPR{}
+pub enum C4{}
+pub enum C8{}
 
 // convblock impls (used in Converter trait)
+// Blocks are handed over as RGBA pixels, row by row, and written to a slice of one
+// block stride.
 impl I4 {
-    fn convblock(_block: &Vec<u8>) -> Vec<u8> {
-        vec![]
+    fn convblock(block: &[u8], out: &mut [u8]) {
+        for (dst, pair) in out.iter_mut().zip(block.chunks_exact(8)) {
+            let first = scaledown(intensity(&pair[0..4]), 4);
+            let second = scaledown(intensity(&pair[4..8]), 4);
+            *dst = first << 4 | second;
+        }
     }
 }
 impl I8 {
-    fn convblock(_block: &Vec<u8>) -> Vec<u8> {
-        vec![]
+    fn convblock(block: &[u8], out: &mut [u8]) {
+        for (dst, pix) in out.iter_mut().zip(block.chunks_exact(4)) {
+            *dst = intensity(pix);
+        }
     }
 }
 impl IA4 {
-    fn convblock(_block: &Vec<u8>) -> Vec<u8> {
-        vec![]
+    fn convblock(block: &[u8], out: &mut [u8]) {
+        for (dst, pix) in out.iter_mut().zip(block.chunks_exact(4)) {
+            *dst = scaledown(pix[3], 4) << 4 | scaledown(intensity(pix), 4);
+        }
     }
 }
 impl IA8 {
-    fn convblock(_block: &Vec<u8>) -> Vec<u8> {
-        vec![]
+    fn convblock(block: &[u8], out: &mut [u8]) {
+        for (dst, pix) in out.chunks_exact_mut(2).zip(block.chunks_exact(4)) {
+            dst.copy_from_slice(&[pix[3], intensity(pix)]);
+        }
     }
 }
 impl RGB565 {
-    fn convblock(_block: &Vec<u8>) -> Vec<u8> {
-        vec![]
+    fn convblock(block: &[u8], out: &mut [u8]) {
+        for (dst, pix) in out.chunks_exact_mut(2).zip(block.chunks_exact(4)) {
+            dst.copy_from_slice(&rgba8torgb565(pix).to_be_bytes());
+        }
     }
 }
 impl RGB5A3 {
-    fn convblock(_block: &Vec<u8>) -> Vec<u8> {
-        vec![]
+    fn convblock(block: &[u8], out: &mut [u8]) {
+        for (dst, pix) in out.chunks_exact_mut(2).zip(block.chunks_exact(4)) {
+            dst.copy_from_slice(&rgba8torgb5a3(pix).to_be_bytes());
+        }
     }
 }
 impl RGBA32 {
-    fn convblock(_block: &Vec<u8>) -> Vec<u8> {
-        vec![]
+    fn convblock(block: &[u8], out: &mut [u8]) {
+        // The first half of the block holds the AR pairs, the second half the GB pairs.
+        let (ar, gb) = out.split_at_mut(32);
+        for ((ar, gb), pix) in ar.chunks_exact_mut(2).zip(gb.chunks_exact_mut(2)).zip(block.chunks_exact(4)) {
+            ar.copy_from_slice(&[pix[3], pix[0]]);
+            gb.copy_from_slice(&[pix[1], pix[2]]);
+        }
     }
 }
 impl CMPR {
-    fn convblock(block: &Vec<u8>) -> Vec<u8> {
-        let mut result = vec![0u8; 32];
-        let mut subblock = vec![0u8; 64];
+    fn convblock(block: &[u8], out: &mut [u8]) {
+        let mut subblock = [0u8; 64];
         let mut x = 0;
         let mut y = 0;
         for i in 0..(block.len() / 64) {
-            arrcopy(block, x + y + 0, &mut subblock, 0, 16);
-            arrcopy(block, x + y + 32, &mut subblock, 16, 16);
-            arrcopy(block, x + y + 64, &mut subblock, 32, 16);
-            arrcopy(block, x + y + 96, &mut subblock, 48, 16);
+            for row in 0..4 {
+                let sidx = x + y + row * 32;
+                subblock[row * 16..row * 16 + 16].copy_from_slice(&block[sidx..sidx + 16]);
+            }
             x = 16 - x;
             if x == 0 {
                 y = 128;
             }
-            let temp = convblocktoquatercmpr(&subblock);
             let sidx = i << 3;
-            for j in 0..temp.len() {
-                result[sidx+j] = temp[j];
-            }
+            convblocktoquatercmpr(&subblock, &mut out[sidx..sidx + 8]);
+        }
+    }
+}
+
+// The palette formats expect the palette index in the first byte of every pixel.
+impl C4 {
+    fn convblock(block: &[u8], out: &mut [u8]) {
+        for (dst, pair) in out.iter_mut().zip(block.chunks_exact(8)) {
+            *dst = (pair[0] & 0xF) << 4 | (pair[4] & 0xF);
+        }
+    }
+}
+impl C8 {
+    fn convblock(block: &[u8], out: &mut [u8]) {
+        for (dst, pix) in out.iter_mut().zip(block.chunks_exact(4)) {
+            *dst = pix[0];
         }
-        result
     }
 }
 
@@ -86,7 +123,7 @@ macro_rules! impl_converter {
     ($item:tt) => {
         impl Converter for $item {
             const FORMAT: ImageDataFormat = $item;
-            const TO: fn(&Vec<u8>) -> Vec<u8> = $item::convblock;
+            const TO: fn(&[u8], &mut [u8]) = $item::convblock;
         }
     };
     ($arg:tt, $($args:tt),+) => {
@@ -96,10 +133,55 @@ macro_rules! impl_converter {
 }
 
 // Macro uses
-impl_converter!(I4, I8, IA4, IA8, RGB565, RGB5A3, RGBA32, CMPR);
+impl_converter!(I4, I8, IA4, IA8, RGB565, RGB5A3, RGBA32, CMPR, C4, C8);
+
+// The ImageDataFormat `format` is encoded with, None for formats without an encoder.
+pub fn dataformat(format: TextureFormats) -> Option<ImageDataFormat> {
+    Some(match format {
+        TextureFormats::I4 => I4,
+        TextureFormats::I8 => I8,
+        TextureFormats::IA4 => IA4,
+        TextureFormats::IA8 => IA8,
+        TextureFormats::RGB565 => RGB565,
+        TextureFormats::RGB5A3 => RGB5A3,
+        TextureFormats::RGBA32 => RGBA32,
+        TextureFormats::CMPR => CMPR,
+        TextureFormats::C4 => C4,
+        TextureFormats::C8 => C8,
+        TextureFormats::C14X2 => return None
+    })
+}
 
 // Special funcs
-fn distance(color1: &Vec<u8>, off1: usize, color2: &Vec<u8>, off2: usize) -> i32 {
+pub fn intensity(pix: &[u8]) -> u8 {
+    let (r, g, b) = (pix[0] as u32, pix[1] as u32, pix[2] as u32);
+    ((r * 299 + g * 587 + b * 114 + 500) / 1000) as u8
+}
+fn scaledown(value: u8, bits: u32) -> u8 {
+    let max = (1u32 << bits) - 1;
+    ((value as u32 * max + 127) / 255) as u8
+}
+pub fn rgba8torgb565(pix: &[u8]) -> u16 {
+    let r = scaledown(pix[0], 5) as u16;
+    let g = scaledown(pix[1], 6) as u16;
+    let b = scaledown(pix[2], 5) as u16;
+    r << 11 | g << 5 | b
+}
+pub fn rgba8torgb5a3(pix: &[u8]) -> u16 {
+    let a = scaledown(pix[3], 3) as u16;
+    if a == 7 {
+        let r = scaledown(pix[0], 5) as u16;
+        let g = scaledown(pix[1], 5) as u16;
+        let b = scaledown(pix[2], 5) as u16;
+        0x8000 | r << 10 | g << 5 | b
+    } else {
+        let r = scaledown(pix[0], 4) as u16;
+        let g = scaledown(pix[1], 4) as u16;
+        let b = scaledown(pix[2], 4) as u16;
+        a << 12 | r << 8 | g << 4 | b
+    }
+}
+fn distance(color1: &[u8], off1: usize, color2: &[u8], off2: usize) -> i32 {
     let mut temp = 0;
     for i in 0..3 {
         let b1 = color1[off1 + i] as i32;
@@ -109,17 +191,17 @@ fn distance(color1: &Vec<u8>, off1: usize, color2: &Vec<u8>, off2: usize) -> i32
     }
     temp
 }
-fn leastdistance(palette: &Vec<Vec<u8>>, color: &Vec<u8>, off: usize) -> i32 {
+fn leastdistance(palette: &[[u8; 4]], color: &[u8], off: usize) -> i32 {
     if color[off + 3] < 8 {
         return 3;
     }
     let mut dist = i32::MAX;
     let mut best = 0;
-    for i in 0..palette.len() {
-        if palette[i][3] != 0xff {
+    for (i, entry) in palette.iter().enumerate() {
+        if entry[3] != 0xff {
             break;
         }
-        let temp = distance(&palette[i], 0, color, off);
+        let temp = distance(entry, 0, color, off);
         if temp < dist {
             if temp == 0 {
                 return i as i32;
@@ -129,26 +211,24 @@ fn leastdistance(palette: &Vec<Vec<u8>>, color: &Vec<u8>, off: usize) -> i32 {
         }
     }
     best
-} 
-fn arrcopy<T: Copy>(src: &Vec<T>, sidx: usize, dest: &mut Vec<T>, didx: usize, len: usize) {
-    for i in 0..len {
-        dest[didx+i] = src[sidx+i];
-    }
 }
-fn convblocktoquatercmpr(block: &Vec<u8>) -> Vec<u8> {
+fn convblocktoquatercmpr(block: &[u8], result: &mut [u8]) {
     let mut dist = -1;
     let mut col1 = -1;
     let mut col2 = -1;
     let mut alpha = false;
-    let mut result = vec![0u8; 8];
-    let mut palette = vec![vec![0u8; 4]; 4];
-    for i in 0..15 {
+    let mut palette = [[0u8; 4]; 4];
+    for i in 0..16 {
         if block[i * 4 + 3] < 16 {
             alpha = true;
         } else {
+            // Pick the two opaque colors furthest apart as the endpoints.
             for j in (i + 1)..16 {
+                if block[j * 4 + 3] < 16 {
+                    continue;
+                }
                 let temp = distance(block, i * 4, block, j * 4);
-                if temp < dist {
+                if temp > dist {
                     dist = temp;
                     col1 = i as i32;
                     col2 = j as i32;
@@ -157,14 +237,19 @@ fn convblocktoquatercmpr(block: &Vec<u8>) -> Vec<u8> {
         }
     }
     if dist == -1 {
-        palette[0] = vec![0, 0, 0, 0xff];
+        palette[0] = [0, 0, 0, 0xff];
         palette[1].fill(0x0);
         palette[2].fill(0x0);
         palette[3].fill(0x0);
+        // A single opaque pixel never forms a pair, keep its color.
+        if let Some(i) = (0..16).find(|i| block[i * 4 + 3] >= 16) {
+            palette[0][0..3].copy_from_slice(&block[i * 4..i * 4 + 3]);
+            palette[1] = palette[0];
+        }
     } else {
-        arrcopy(block, col1 as usize * 4, &mut palette[0], 0, 3);
+        palette[0][0..3].copy_from_slice(&block[col1 as usize * 4..col1 as usize * 4 + 3]);
         palette[0][3] = 0xff;
-        arrcopy(block, col2 as usize * 4, &mut palette[1], 0, 3);
+        palette[1][0..3].copy_from_slice(&block[col2 as usize * 4..col2 as usize * 4 + 3]);
         palette[1][3] = 0xff;
 
         if palette[0][0] >> 3 == palette[1][0] >> 3 && palette[0][1] >> 2 == palette[1][1] >> 2 && palette[0][2] >> 3 == palette[1][2] >> 3 {
@@ -179,46 +264,42 @@ fn convblocktoquatercmpr(block: &Vec<u8>) -> Vec<u8> {
             }
         }
     }
-    result[0] = (palette[0][2] as i32 & 0xf8 | palette[0][1] as i32 >> 5) as u8;
-    result[1] = ((palette[0][1] as i32) << 3 & 0xe0 | palette[0][0] as i32 >> 3) as u8;
-    result[2] = (palette[1][2] as i32 & 0xf8 | palette[1][1] as i32 >> 5) as u8;
-    result[3] = ((palette[1][1] as i32) << 3 & 0xe0 | palette[1][0] as i32 >> 3) as u8;
+    result[0] = (palette[0][0] as i32 & 0xf8 | palette[0][1] as i32 >> 5) as u8;
+    result[1] = (((palette[0][1] as i32) << 3) & 0xe0 | palette[0][2] as i32 >> 3) as u8;
+    result[2] = (palette[1][0] as i32 & 0xf8 | palette[1][1] as i32 >> 5) as u8;
+    result[3] = (((palette[1][1] as i32) << 3) & 0xe0 | palette[1][2] as i32 >> 3) as u8;
 
     if (result[0] > result[2] || (result[0] == result[2] && result[1] >= result[3])) == alpha {
         result.copy_within(0..2, 4);
         result.copy_within(2..4, 0);
         result.copy_within(4..6, 2);
 
-        palette[2] = palette[0].clone();
-        palette[0] = palette[1].clone();
-        palette[1] = palette[2].clone();
+        palette.swap(0, 1);
     }
 
     if !alpha {
-        let mut bits = vec![0xffu8; 4];
+        let mut bits = [0xffu8; 4];
         for i in 0..3 {
             bits[i] = ((((palette[0][i] as i32) << 1) + palette[1][i] as i32) / 3) as u8;
         }
-        palette[2].copy_from_slice(&bits);
-        bits.fill(0xff);
+        palette[2] = bits;
         for i in 0..3 {
             bits[i] = ((palette[0][i] as i32 + ((palette[1][i] as i32) << 1)) / 3) as u8;
         }
-        palette[3].copy_from_slice(&bits);
+        palette[3] = bits;
     } else {
-        let mut bits = vec![0xffu8; 4];
+        let mut bits = [0xffu8; 4];
         for i in 0..3 {
             bits[i] = ((palette[0][i] as i32 + palette[1][i] as i32) >> 1) as u8;
         }
-        palette[2].copy_from_slice(&bits);
+        palette[2] = bits;
         palette[3].fill(0x0);
     }
     for i in 0..(block.len() >> 4) {
-        let mut num = leastdistance(&palette, block, i * 16 + 0) << 6;
+        let mut num = leastdistance(&palette, block, i * 16) << 6;
         num |= leastdistance(&palette, block, i * 16 + 4) << 4;
         num |= leastdistance(&palette, block, i * 16 + 8) << 2;
         num |= leastdistance(&palette, block, i * 16 + 12);
         result[4 + i] = num as u8;
     }
-    result
-}
\ No newline at end of file
+}
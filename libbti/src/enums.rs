@@ -1,8 +1,31 @@
+#[cfg(feature = "binrw")]
 use binrw::prelude::*;
-use std::default::Default;
+use core::default::Default;
+use core::str::FromStr;
+use alloc::{format, string::String, vec::Vec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-#[derive(BinRead, Debug, Clone, Copy, PartialEq, Eq, Default)]
-#[br(repr = u8)]
+// Parses the variant names, ignoring case.
+macro_rules! impl_fromstr {
+    ($item:ident, $kind:literal, [$($variant:ident),+]) => {
+        impl FromStr for $item {
+            type Err = String;
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let items = [$($item::$variant),+];
+                let names = [$(stringify!($variant)),+];
+                let pos = names.iter().position(|x| x.eq_ignore_ascii_case(s))
+                .ok_or_else(|| format!("Unknown {} \"{}\", expected one of {}.", $kind, s, names.join(", ")))?;
+                Ok(items[pos])
+            }
+        }
+    };
+}
+pub(crate) use impl_fromstr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "binrw", derive(BinRead), br(repr = u8))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(u8)]
 pub enum TextureFormats {
     #[default] I4 = 0x00,
@@ -22,16 +45,17 @@ impl<N: Into<u8>> From<N> for TextureFormats {
     fn from(n: N) -> Self {
         let u: u8 = n.into();
         use TextureFormats::*;
-        let items = 
-        vec![I4, I8, IA4, IA8, RGB565, RGB5A3, RGBA32, C4, C8, C14X2, CMPR];
+        let items =
+        [I4, I8, IA4, IA8, RGB565, RGB5A3, RGBA32, C4, C8, C14X2, CMPR];
         let nums = items.iter().map(|x| *x as u8).collect::<Vec<u8>>();
         let pos = nums.iter().position(|x| *x == u).unwrap();
         items[pos]
     }
 }
 
-#[derive(BinRead, Debug, Clone, Copy, PartialEq, Eq, Default)]
-#[br(repr = u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "binrw", derive(BinRead), br(repr = u8))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(u8)]
 pub enum WrapNodes {
     #[default] ClampToEdge = 0,
@@ -43,15 +67,16 @@ impl<N: Into<u8>> From<N> for WrapNodes {
     fn from(n: N) -> Self {
         let u: u8 = n.into();
         use WrapNodes::*;
-        let items = vec![ClampToEdge, Repeat, MirroredRepeat];
+        let items = [ClampToEdge, Repeat, MirroredRepeat];
         let nums = items.iter().map(|x| *x as u8).collect::<Vec<u8>>();
         let pos = nums.iter().position(|x| *x == u).unwrap();
         items[pos]
     }
 }
 
-#[derive(BinRead, Debug, Clone, Copy, PartialEq, Eq, Default)]
-#[br(repr = u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "binrw", derive(BinRead), br(repr = u8))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(u8)]
 pub enum PaletteFormats {
     #[default] IA8 = 0x00,
@@ -63,15 +88,16 @@ impl<N: Into<u8>> From<N> for PaletteFormats {
     fn from(n: N) -> Self {
         let u: u8 = n.into();
         use PaletteFormats::*;
-        let items = vec![IA8, RGB565, RGB5A3];
+        let items = [IA8, RGB565, RGB5A3];
         let nums = items.iter().map(|x| *x as u8).collect::<Vec<u8>>();
         let pos = nums.iter().position(|x| *x == u).unwrap();
         items[pos]
     }
 }
 
-#[derive(BinRead, Debug, Clone, Copy, PartialEq, Eq, Default)]
-#[br(repr = u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "binrw", derive(BinRead), br(repr = u8))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(u8)]
 pub enum FilterMode {
     #[default] Nearest = 0x0,
@@ -86,10 +112,84 @@ impl<N: Into<u8>> From<N> for FilterMode {
     fn from(n: N) -> Self {
         let u = n.into();
         use FilterMode::*;
-        let items = vec![Nearest, Linear, NearestMipmapNearest, NearestMipmapLinear,
+        let items = [Nearest, Linear, NearestMipmapNearest, NearestMipmapLinear,
         LinearMipmapNearest, LinearMipmapLinear];
         let nums = items.iter().map(|x| *x as u8).collect::<Vec<u8>>();
         let pos = nums.iter().position(|x| *x == u).unwrap();
         items[pos]
     }
-}
\ No newline at end of file
+}
+
+// Maximum anisotropic filtering, GX_ANISO_1/2/4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "binrw", derive(BinRead), br(repr = u8))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(u8)]
+pub enum Anisotropy {
+    #[default] One = 0x0,
+    Two = 0x1,
+    Four = 0x2,
+}
+
+impl<N: Into<u8>> From<N> for Anisotropy {
+    fn from(n: N) -> Self {
+        let u = n.into();
+        use Anisotropy::*;
+        let items = [One, Two, Four];
+        let nums = items.iter().map(|x| *x as u8).collect::<Vec<u8>>();
+        let pos = nums.iter().position(|x| *x == u).unwrap();
+        items[pos]
+    }
+}
+
+// Alpha setting of the header as J3D uses it. Opaque textures ignore the alpha channel,
+// OneBit is for cutouts and Full for translucent textures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "binrw", derive(BinRead), br(repr = u8))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(u8)]
+pub enum AlphaSetting {
+    #[default] Opaque = 0x0,
+    OneBit = 0x1,
+    Full = 0x2,
+}
+
+impl<N: Into<u8>> From<N> for AlphaSetting {
+    fn from(n: N) -> Self {
+        let u = n.into();
+        use AlphaSetting::*;
+        let items = [Opaque, OneBit, Full];
+        let nums = items.iter().map(|x| *x as u8).collect::<Vec<u8>>();
+        let pos = nums.iter().position(|x| *x == u).unwrap();
+        items[pos]
+    }
+}
+
+impl_fromstr!(TextureFormats, "texture format",
+    [I4, I8, IA4, IA8, RGB565, RGB5A3, RGBA32, C4, C8, C14X2, CMPR]);
+impl_fromstr!(WrapNodes, "wrap mode", [ClampToEdge, Repeat, MirroredRepeat]);
+impl_fromstr!(PaletteFormats, "palette format", [IA8, RGB565, RGB5A3]);
+impl_fromstr!(FilterMode, "filter mode", [Nearest, Linear, NearestMipmapNearest,
+    NearestMipmapLinear, LinearMipmapNearest, LinearMipmapLinear]);
+impl_fromstr!(Anisotropy, "anisotropy", [One, Two, Four]);
+// Channel order of decoded pixels. The library itself always works on RGBA, BGRA is
+// offered by the decoders for consumers uploading to APIs that expect it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PixelLayout {
+    #[default] Rgba,
+    Bgra,
+}
+
+impl PixelLayout {
+    // Puts an RGBA color into this layout.
+    pub fn arrange(self, color: [u8; 4]) -> [u8; 4] {
+        match self {
+            PixelLayout::Rgba => color,
+            PixelLayout::Bgra => [color[2], color[1], color[0], color[3]],
+        }
+    }
+}
+
+impl_fromstr!(PixelLayout, "pixel layout", [Rgba, Bgra]);
+impl_fromstr!(AlphaSetting, "alpha setting", [Opaque, OneBit, Full]);
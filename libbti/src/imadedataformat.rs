@@ -1,8 +1,15 @@
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
 #[derive(Debug, Default, Clone)]
 pub struct ImageDataFormat {
     pub name: &'static str,
     pub desc: &'static str,
     pub bitsperpixel: i32,
+    // Bits stored per RGB channel, intensity formats store one value with that depth and
+    // palette formats leave it to the palette entries.
+    pub colordepth: [i32; 3],
     pub alphadepth: i32,
     pub blockwidth: i32,
     pub blockheight: i32,
@@ -16,11 +23,12 @@ pub struct ImageDataFormat {
 }
 
 impl ImageDataFormat {
-    pub const fn new(name: &'static str, desc: &'static str, bitsperpixel: i32, alphadepth: i32,
+    #[allow(clippy::too_many_arguments)]
+    pub const fn new(name: &'static str, desc: &'static str, bitsperpixel: i32, colordepth: [i32; 3], alphadepth: i32,
     blockwidth: i32, blockheight: i32, blockstride: i32, hascolor: bool, iscompressed: bool,
     lossy: bool, palette: bool, palettesize: i32, palettebitsperentry: i32) -> Self {
         Self {
-            name, desc, bitsperpixel, alphadepth, blockwidth, blockheight, blockstride, hascolor,
+            name, desc, bitsperpixel, colordepth, alphadepth, blockwidth, blockheight, blockstride, hascolor,
             iscompressed, lossy, palette, palettesize, palettebitsperentry
         }
     }
@@ -34,42 +42,51 @@ impl ImageDataFormat {
 
 pub trait Converter {
     const FORMAT: ImageDataFormat;
-    const TO: fn(&Vec<u8>) -> Vec<u8>;
-    fn convertto(data: &Vec<u8>, width: i32, height: i32) -> Vec<u8> {
+    const TO: fn(&[u8], &mut [u8]);
+    fn convertto(data: &[u8], width: i32, height: i32) -> Vec<u8> {
+        Self::convertwith(data, width, height, &|| {})
+    }
+    // Rows of blocks are encoded in parallel with the rayon feature, every worker reuses
+    // one block buffer. `rowdone` is called after each row, possibly from several threads.
+    fn convertwith(data: &[u8], width: i32, height: i32, rowdone: &(dyn Fn() + Sync)) -> Vec<u8> {
         let ImageDataFormat {
             blockheight, blockwidth, blockstride, ..
         } = Self::FORMAT;
-        let ressize = Self::FORMAT.roundwidth(width) /
-        blockwidth * Self::FORMAT.roundheight(height) / blockheight * blockstride;
+        let rowsize = (Self::FORMAT.roundwidth(width) / blockwidth * blockstride) as usize;
+        let ressize = Self::FORMAT.roundheight(height) / blockheight * rowsize as i32;
         let mut result = vec![0u8; ressize as usize];
-        let mut block = vec![0u8; (blockwidth * blockheight << 2) as usize];
-        let mut i = 0;
-        for y in (0..height).step_by(blockheight as usize) {
-            let y = y as usize;
-            for x in (0..width).step_by(blockwidth as usize) {
-                let x = x as usize;
-                block.fill(0x0);
-                let blockheight = blockheight as usize;
-                let height = height as usize;
-                let blockwidth = blockwidth as usize;
-                let width = width as usize;
-                for dy in 0..(blockheight.min(height)) {
-                    let sidx = ((y + dy) * width + x) << 2;
-                    let didx = dy * blockwidth << 2;
-                    let len = blockwidth.min(width - x) << 2;
-                    for j in 0..len {
-                        block[didx+j] = data[sidx+j];
-                    }
-                }
-                let blockresult = Self::TO(&block);
-                let sidx = (i * blockstride) as usize;
-                for j in 0..blockresult.len() {
-                    result[sidx+j] = blockresult[j];
-                }
-            }
-            i += 1;
+        if rowsize == 0 {
+            return result;
+        }
+        let blocksize = ((blockwidth * blockheight) << 2) as usize;
+        let convert = |block: &mut Vec<u8>, (row, out): (usize, &mut [u8])| {
+            Self::convertrow(data, width as usize, height as usize, row, block, out);
+            rowdone();
+        };
+        #[cfg(feature = "rayon")]
+        result.par_chunks_mut(rowsize).enumerate().for_each_init(|| vec![0u8; blocksize], convert);
+        #[cfg(not(feature = "rayon"))]
+        {
+            let mut block = vec![0u8; blocksize];
+            result.chunks_mut(rowsize).enumerate().for_each(|x| convert(&mut block, x));
         }
         result
     }
-
-}
\ No newline at end of file
+    // Encodes the `row`th row of blocks into `out`, `block` is scratch space for one block.
+    fn convertrow(data: &[u8], width: usize, height: usize, row: usize, block: &mut [u8], out: &mut [u8]) {
+        let blockheight = Self::FORMAT.blockheight as usize;
+        let blockwidth = Self::FORMAT.blockwidth as usize;
+        let blockstride = Self::FORMAT.blockstride as usize;
+        let y = row * blockheight;
+        for (i, x) in (0..width).step_by(blockwidth).enumerate() {
+            block.fill(0x0);
+            for dy in 0..(blockheight.min(height - y)) {
+                let sidx = ((y + dy) * width + x) << 2;
+                let didx = (dy * blockwidth) << 2;
+                let len = blockwidth.min(width - x) << 2;
+                block[didx..didx + len].copy_from_slice(&data[sidx..sidx + len]);
+            }
+            Self::TO(block, &mut out[i * blockstride..(i + 1) * blockstride]);
+        }
+    }
+}
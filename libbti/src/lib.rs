@@ -1,3 +1,6 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+extern crate alloc;
+
 pub mod enums;
 pub mod bti;
 pub mod palette;
@@ -5,4 +8,26 @@ pub mod prelude;
 pub mod decoders;
 pub mod range;
 pub mod imadedataformat;
-pub mod encoders;
\ No newline at end of file
+pub mod encoders;
+#[cfg(feature = "image")]
+pub mod quality;
+#[cfg(feature = "image")]
+pub mod selector;
+#[cfg(feature = "image")]
+pub mod diff;
+pub mod mipmap;
+pub mod simd;
+pub mod options;
+pub mod settings;
+pub mod builder;
+pub mod alpha;
+pub mod preprocess;
+pub mod dither;
+#[cfg(feature = "std")]
+pub mod sampler;
+#[cfg(feature = "binrw")]
+pub mod view;
+#[cfg(all(feature = "image", feature = "binrw"))]
+pub mod codec;
+#[cfg(feature = "mmap")]
+pub mod mapped;
@@ -0,0 +1,29 @@
+use memmap2::Mmap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use crate::view::BtiView;
+
+// A memory mapped file, pages are only read from disk once they are touched. Parsing
+// a view of it reads the header and nothing else.
+pub struct MappedFile {
+    map: Mmap,
+}
+
+impl MappedFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // The mapping is only valid as long as nobody truncates the file behind our back,
+        // which is the usual deal for tools reading extracted game files.
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(Self { map })
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    pub fn view(&self) -> Result<BtiView<'_>, String> {
+        BtiView::parse(&self.map)
+    }
+}
@@ -0,0 +1,42 @@
+use alloc::{vec, vec::Vec};
+
+// Number of mip levels down to 1x1, including the full size image.
+pub fn maxmipmaps(width: u16, height: u16) -> u8 {
+    let largest = width.max(height).max(1);
+    (16 - largest.leading_zeros()) as u8
+}
+
+pub fn levelsize(width: u16, height: u16, level: u8) -> (u16, u16) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
+
+// Halves an RGBA image with a box filter, odd edges are clamped.
+pub fn downsample(data: &[u8], width: u16, height: u16) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let (dw, dh) = ((width / 2).max(1), (height / 2).max(1));
+    let mut res = vec![0u8; dw * dh * 4];
+    for y in 0..dh {
+        for x in 0..dw {
+            let (x0, y0) = ((x * 2).min(width - 1), (y * 2).min(height - 1));
+            let (x1, y1) = ((x * 2 + 1).min(width - 1), (y * 2 + 1).min(height - 1));
+            for c in 0..4 {
+                let sum = data[(y0 * width + x0) * 4 + c] as u32 + data[(y0 * width + x1) * 4 + c] as u32
+                + data[(y1 * width + x0) * 4 + c] as u32 + data[(y1 * width + x1) * 4 + c] as u32;
+                res[(y * dw + x) * 4 + c] = ((sum + 2) / 4) as u8;
+            }
+        }
+    }
+    res
+}
+
+// Returns `count` levels starting with a copy of the full size image.
+pub fn generatemips(data: &[u8], width: u16, height: u16, count: u8) -> Vec<Vec<u8>> {
+    let count = count.clamp(1, maxmipmaps(width, height));
+    let mut res = vec![data.to_vec()];
+    for level in 1..count {
+        let (w, h) = levelsize(width, height, level - 1);
+        let next = downsample(&res[level as usize - 1], w, h);
+        res.push(next);
+    }
+    res
+}
@@ -0,0 +1,55 @@
+use core::str::FromStr;
+use alloc::{format, string::String};
+use crate::enums::impl_fromstr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Premultiply {
+    // Pixels are encoded as they are.
+    #[default] Keep,
+    // Straight alpha input, the texture gets premultiplied colors.
+    Premultiply,
+    // Premultiplied input, the texture gets straight colors.
+    Unpremultiply,
+}
+
+impl_fromstr!(Premultiply, "premultiply mode", [Keep, Premultiply, Unpremultiply]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dither {
+    #[default] None,
+    // Ordered dithering with an 8x8 matrix, stable patterns that compress well.
+    Bayer,
+    // Error diffusion.
+    FloydSteinberg,
+    Atkinson,
+}
+
+impl_fromstr!(Dither, "dither mode", [None, Bayer, FloydSteinberg, Atkinson]);
+
+// Everything that changes how pixels get encoded, the defaults match `BTI::encodedata`.
+#[derive(Clone, Copy, Default)]
+pub struct EncodeOptions<'a> {
+    // Called with the finished and the total number of block rows of all mip levels.
+    // With the rayon feature it may be called from several threads at once.
+    pub progress: Option<&'a (dyn Fn(usize, usize) + Sync)>,
+    // Fills the color of fully transparent pixels from their visible neighbours, so CMPR
+    // and mip levels don't get dark halos around cutouts.
+    pub bleed: bool,
+    pub premultiply: Premultiply,
+    // Alpha test reference, smaller levels are adjusted to let as many pixels pass as the
+    // first one does instead of fading away.
+    pub alphacoverage: Option<u8>,
+    // Dithering for formats with less than 8 bits per channel and C4/C8 palettes, every
+    // level is dithered on its own. CMPR is left alone. C4/C8 palettes hold the exact
+    // colors of the image, so there it only helps the mip levels.
+    pub dither: Dither,
+}
+
+impl EncodeOptions<'_> {
+    // True if the pixels are changed before encoding, the original bytes of a texture
+    // that was read can't be reused then.
+    pub fn preprocesses(&self) -> bool {
+        self.bleed || self.premultiply != Premultiply::Keep || self.alphacoverage.is_some()
+        || self.dither != Dither::None
+    }
+}
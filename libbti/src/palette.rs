@@ -1,15 +1,92 @@
+#[cfg(feature = "std")]
 use std::io::Read;
+use alloc::{vec, vec::Vec};
+use crate::prelude::{PaletteFormats, rgb565torgba8, rgb5a3torgba8, intensity,
+    rgba8torgb565, rgba8torgb5a3};
 
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Palette {
     pub palettedata: Vec<u8>
 }
 
 impl Palette {
+    // Every palette entry is two bytes wide.
+    #[cfg(feature = "std")]
     pub fn read<R: Read, N: Into<usize> + Copy>(reader: &mut R, count: N) -> Self {
-        let mut res = Palette::default();
-        res.palettedata = vec![0u8; count.into()];
+        let mut res = Palette {
+            palettedata: vec![0u8; count.into() * 2]
+        };
         reader.read_exact(&mut res.palettedata).unwrap();
         res
     }
+
+    // Builds a palette holding every distinct color of the RGBA pixels, or None if
+    // there are more than `maxcolors` of them once converted to the palette format.
+    pub fn generate(data: &[u8], format: PaletteFormats, maxcolors: usize) -> Option<Self> {
+        let mut entries: Vec<u16> = vec![];
+        for pix in data.chunks_exact(4) {
+            let entry = packpixel(pix, format);
+            if !entries.contains(&entry) {
+                if entries.len() == maxcolors {
+                    return None;
+                }
+                entries.push(entry);
+            }
+        }
+        Some(Palette {
+            palettedata: entries.iter().flat_map(|x| x.to_be_bytes()).collect()
+        })
+    }
+
+    pub fn count(&self) -> usize {
+        self.palettedata.len() / 2
+    }
+
+    // The palette entries as RGBA colors.
+    pub fn colors(&self, format: PaletteFormats) -> Vec<[u8; 4]> {
+        self.palettedata.chunks_exact(2).map(|entry| {
+            let mut color = [0u8; 4];
+            match format {
+                PaletteFormats::IA8 => {
+                    color[0..3].fill(entry[1]);
+                    color[3] = entry[0];
+                },
+                PaletteFormats::RGB565 => {
+                    rgb565torgba8(u16::from_be_bytes([entry[0], entry[1]]), &mut color, 0)
+                },
+                PaletteFormats::RGB5A3 => {
+                    rgb5a3torgba8(u16::from_be_bytes([entry[0], entry[1]]), &mut color, 0)
+                }
+            }
+            color
+        }).collect()
+    }
+
+    // Maps every RGBA pixel to the index of the closest palette entry.
+    pub fn indices(&self, data: &[u8], format: PaletteFormats) -> Vec<u8> {
+        let colors = self.colors(format);
+        let mut lastpix = None;
+        let mut lastidx = 0u8;
+        data.chunks_exact(4).map(|pix| {
+            if lastpix != Some(pix) {
+                lastpix = Some(pix);
+                lastidx = colors.iter().enumerate().min_by_key(|(_, color)| {
+                    color.iter().zip(pix).map(|(a, b)| {
+                        let diff = *a as i32 - *b as i32;
+                        diff * diff
+                    }).sum::<i32>()
+                }).map(|(i, _)| i as u8).unwrap_or(0);
+            }
+            lastidx
+        }).collect()
+    }
+}
+
+fn packpixel(pix: &[u8], format: PaletteFormats) -> u16 {
+    match format {
+        PaletteFormats::IA8 => u16::from_be_bytes([pix[3], intensity(pix)]),
+        PaletteFormats::RGB565 => rgba8torgb565(pix),
+        PaletteFormats::RGB5A3 => rgba8torgb5a3(pix),
+    }
 }
\ No newline at end of file
@@ -1,2 +1,13 @@
-pub use {crate::{enums::*, palette::*, enums::*, bti::*, decoders::*, range::*, imadedataformat::*, encoders::*}, 
-image, binrw};
\ No newline at end of file
+pub use crate::{enums::*, palette::*, bti::*, decoders::*, range::*, imadedataformat::*, encoders::*, mipmap::*,
+simd::*, options::*, settings::*, builder::*, alpha::*,
+preprocess::*, dither::*};
+#[cfg(feature = "std")]
+pub use crate::sampler::*;
+#[cfg(feature = "image")]
+pub use {crate::{quality::*, selector::*, diff::*}, image};
+#[cfg(feature = "binrw")]
+pub use {crate::view::*, binrw};
+#[cfg(all(feature = "image", feature = "binrw"))]
+pub use crate::codec::*;
+#[cfg(feature = "mmap")]
+pub use crate::mapped::*;
@@ -0,0 +1,178 @@
+use alloc::{vec, vec::Vec};
+use crate::prelude::{EncodeOptions, Premultiply};
+
+// Gives fully transparent pixels the average color of their visible neighbours, growing
+// outwards one ring at a time until the whole image is filled. Their alpha stays 0, only
+// the garbage RGB that CMPR endpoints and mip levels would pick up is replaced.
+pub fn bleed(data: &mut [u8], width: usize, height: usize) {
+    let mut known = data.chunks_exact(4).map(|x| x[3] != 0).collect::<Vec<bool>>();
+    if known.iter().all(|x| *x) || !known.iter().any(|x| *x) {
+        return;
+    }
+    let neighbours = |idx: usize| {
+        let (x, y) = ((idx % width) as isize, (idx / width) as isize);
+        (-1..=1).flat_map(move |dy| (-1..=1).map(move |dx| (x + dx, y + dy)))
+        .filter(move |&(nx, ny)| (nx, ny) != (x, y) && nx >= 0 && ny >= 0
+            && (nx as usize) < width && (ny as usize) < height)
+        .map(move |(nx, ny)| ny as usize * width + nx as usize)
+    };
+    let mut queued = known.clone();
+    let mut frontier = vec![];
+    for idx in 0..known.len() {
+        if !known[idx] && neighbours(idx).any(|x| known[x]) {
+            queued[idx] = true;
+            frontier.push(idx);
+        }
+    }
+    while !frontier.is_empty() {
+        for &idx in &frontier {
+            let (mut sum, mut count) = ([0u32; 3], 0);
+            for n in neighbours(idx).filter(|x| known[*x]) {
+                for c in 0..3 {
+                    sum[c] += data[n * 4 + c] as u32;
+                }
+                count += 1;
+            }
+            for c in 0..3 {
+                data[idx * 4 + c] = ((sum[c] + count / 2) / count) as u8;
+            }
+        }
+        // Only mark the ring as known once all of it is filled, so the result doesn't
+        // depend on the order pixels are visited in.
+        for &idx in &frontier {
+            known[idx] = true;
+        }
+        let mut next = vec![];
+        for &idx in &frontier {
+            for n in neighbours(idx) {
+                if !queued[n] {
+                    queued[n] = true;
+                    next.push(n);
+                }
+            }
+        }
+        frontier = next;
+    }
+}
+
+pub fn premultiply(data: &mut [u8]) {
+    for pix in data.chunks_exact_mut(4) {
+        let a = pix[3] as u32;
+        for x in &mut pix[..3] {
+            *x = ((*x as u32 * a + 127) / 255) as u8;
+        }
+    }
+}
+
+// Fully transparent pixels keep their color, there is nothing to divide by.
+pub fn unpremultiply(data: &mut [u8]) {
+    for pix in data.chunks_exact_mut(4) {
+        let a = pix[3] as u32;
+        if a == 0 {
+            continue;
+        }
+        for x in &mut pix[..3] {
+            *x = ((*x as u32 * 255 + a / 2) / a).min(0xFF) as u8;
+        }
+    }
+}
+
+// Number of pixels passing an alpha test against `reference`.
+pub fn coverage(data: &[u8], reference: u8) -> usize {
+    data.chunks_exact(4).filter(|x| x[3] >= reference).count()
+}
+
+// Scales the alpha of `data` so that `target` pixels pass the alpha test, as close as
+// possible. Averaging alpha in smaller levels makes cutouts fade away otherwise.
+pub fn scalecoverage(data: &mut [u8], reference: u8, target: usize) {
+    // Scale in 1/256 steps, the largest one makes every pixel with any alpha pass.
+    let scaled = |a: u8, scale: u32| (a as u32 * scale / 256).min(0xFF) as u8;
+    let passing = |scale: u32| data.chunks_exact(4).filter(|x| scaled(x[3], scale) >= reference).count();
+    if passing(256) == target {
+        return;
+    }
+    let (mut low, mut high) = (0u32, 256 * 256);
+    while low < high {
+        let mid = (low + high) / 2;
+        if passing(mid) >= target {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    for pix in data.chunks_exact_mut(4) {
+        pix[3] = scaled(pix[3], low);
+    }
+}
+
+// Runs everything `options` asks for on the full size image, in the order that keeps the
+// colors meaningful: unpremultiply, bleed, premultiply.
+pub fn prepare(data: &mut [u8], width: usize, height: usize, options: &EncodeOptions) {
+    if options.premultiply == Premultiply::Unpremultiply {
+        unpremultiply(data);
+    }
+    if options.bleed {
+        bleed(data, width, height);
+    }
+    if options.premultiply == Premultiply::Premultiply {
+        premultiply(data);
+    }
+}
+
+// With `alphacoverage` every smaller level keeps the share of pixels passing the alpha
+// test that the first one has.
+pub fn preparemips(levels: &mut [Vec<u8>], options: &EncodeOptions) {
+    let Some(reference) = options.alphacoverage else {
+        return;
+    };
+    let (first, rest) = levels.split_first_mut().unwrap();
+    let (passing, total) = (coverage(first, reference), first.len() / 4);
+    // Nothing passes the test, so it isn't an alpha tested texture.
+    if passing == 0 {
+        return;
+    }
+    for level in rest {
+        let target = (passing * (level.len() / 4) + total / 2) / total;
+        scalecoverage(level, reference, target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bleed_fills_rings() {
+        // Two visible pixels at the ends of a row, the gaps fill from the nearest ring inwards.
+        let mut data = vec![200, 100, 0, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 50, 101, 255];
+        bleed(&mut data, 5, 1);
+        assert_eq!(data, [200, 100, 0, 255, 200, 100, 0, 0, 100, 75, 51, 0, 0, 50, 101, 0, 0, 50, 101, 255]);
+        let mut empty = vec![9, 9, 9, 0, 9, 9, 9, 0];
+        bleed(&mut empty, 2, 1);
+        assert_eq!(empty, [9, 9, 9, 0, 9, 9, 9, 0]);
+    }
+
+    #[test]
+    fn premultiply_rounds() {
+        let mut data = vec![200, 100, 50, 128, 30, 40, 50, 0];
+        premultiply(&mut data);
+        assert_eq!(data, [100, 50, 25, 128, 0, 0, 0, 0]);
+        unpremultiply(&mut data);
+        assert_eq!(data, [199, 100, 50, 128, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn mips_keep_coverage() {
+        // Half of the first level passes the test, so two of the four pixels of the next level should.
+        let first = (0..16).flat_map(|i| [0, 0, 0, if i % 2 == 0 { 255 } else { 0 }]).collect::<Vec<u8>>();
+        let second = [200, 120, 90, 40].iter().flat_map(|&a| [0, 0, 0, a]).collect::<Vec<u8>>();
+        let mut levels = vec![first.clone(), second.clone()];
+        preparemips(&mut levels, &EncodeOptions { alphacoverage: Some(128), ..Default::default() });
+        assert_eq!(levels[0], first);
+        assert_eq!(levels[1].chunks_exact(4).map(|x| x[3]).collect::<Vec<_>>(), [214, 128, 96, 42]);
+        assert_eq!(coverage(&levels[1], 128), 2);
+        let mut levels = vec![first, second.clone()];
+        preparemips(&mut levels, &EncodeOptions::default());
+        assert_eq!(levels[1], second);
+    }
+}
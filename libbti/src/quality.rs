@@ -0,0 +1,185 @@
+use std::fmt;
+use image::{Rgba, RgbaImage};
+
+pub const CHANNELNAMES: [&str; 4] = ["R", "G", "B", "A"];
+
+const SSIMWINDOW: usize = 8;
+const SSIMSTEP: usize = 4;
+const SSIMC1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const SSIMC2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+// Per channel error statistics, all arrays are in R, G, B, A order.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct QualityReport {
+    pub psnr: [f64; 4],
+    pub ssim: [f64; 4],
+    pub maxerror: [u8; 4],
+}
+
+impl QualityReport {
+    pub fn minpsnr(&self) -> f64 {
+        self.psnr.iter().copied().fold(f64::INFINITY, f64::min)
+    }
+
+    pub fn minssim(&self) -> f64 {
+        self.ssim.iter().copied().fold(f64::INFINITY, f64::min)
+    }
+
+    pub fn maxerror(&self) -> u8 {
+        self.maxerror.iter().copied().max().unwrap_or(0)
+    }
+
+    pub fn meets(&self, minpsnr: f64, minssim: f64) -> bool {
+        self.minpsnr() >= minpsnr && self.minssim() >= minssim
+    }
+}
+
+impl fmt::Display for QualityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (c, name) in CHANNELNAMES.iter().enumerate() {
+            if c != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}: PSNR {:>7.2} dB  SSIM {:.4}  max error {:>3}",
+            name, self.psnr[c], self.ssim[c], self.maxerror[c])?;
+        }
+        Ok(())
+    }
+}
+
+// Compares two RGBA buffers of the same size.
+pub fn compare(original: &[u8], encoded: &[u8], width: u32, height: u32) -> QualityReport {
+    let (width, height) = (width as usize, height as usize);
+    assert_eq!(original.len(), width * height * 4);
+    assert_eq!(encoded.len(), original.len());
+    let mut res = QualityReport::default();
+    for c in 0..4 {
+        let mut sqsum = 0f64;
+        let mut maxerror = 0u8;
+        for (a, b) in original.iter().skip(c).step_by(4).zip(encoded.iter().skip(c).step_by(4)) {
+            let diff = a.abs_diff(*b);
+            maxerror = maxerror.max(diff);
+            sqsum += (diff as f64) * (diff as f64);
+        }
+        let mse = sqsum / (width * height).max(1) as f64;
+        res.psnr[c] = if mse == 0.0 {
+            f64::INFINITY
+        } else {
+            10.0 * (255.0 * 255.0 / mse).log10()
+        };
+        res.ssim[c] = ssim(original, encoded, width, height, c);
+        res.maxerror[c] = maxerror;
+    }
+    res
+}
+
+// Mean SSIM of one channel over sliding windows.
+fn ssim(original: &[u8], encoded: &[u8], width: usize, height: usize, channel: usize) -> f64 {
+    if width == 0 || height == 0 {
+        return 1.0;
+    }
+    let winw = SSIMWINDOW.min(width);
+    let winh = SSIMWINDOW.min(height);
+    let mut total = 0f64;
+    let mut count = 0usize;
+    let mut y = 0;
+    loop {
+        let mut x = 0;
+        loop {
+            total += ssimwindow(original, encoded, width, channel, x, y, winw, winh);
+            count += 1;
+            if x + winw >= width {
+                break;
+            }
+            x = (x + SSIMSTEP).min(width - winw);
+        }
+        if y + winh >= height {
+            break;
+        }
+        y = (y + SSIMSTEP).min(height - winh);
+    }
+    total / count as f64
+}
+
+#[allow(clippy::too_many_arguments)]
+fn ssimwindow(original: &[u8], encoded: &[u8], width: usize, channel: usize,
+    x: usize, y: usize, winw: usize, winh: usize) -> f64 {
+    let n = (winw * winh) as f64;
+    let (mut suma, mut sumb, mut sumaa, mut sumbb, mut sumab) = (0f64, 0f64, 0f64, 0f64, 0f64);
+    for wy in y..(y + winh) {
+        for wx in x..(x + winw) {
+            let idx = (wy * width + wx) * 4 + channel;
+            let a = original[idx] as f64;
+            let b = encoded[idx] as f64;
+            suma += a;
+            sumb += b;
+            sumaa += a * a;
+            sumbb += b * b;
+            sumab += a * b;
+        }
+    }
+    let meana = suma / n;
+    let meanb = sumb / n;
+    let vara = sumaa / n - meana * meana;
+    let varb = sumbb / n - meanb * meanb;
+    let covar = sumab / n - meana * meanb;
+    ((2.0 * meana * meanb + SSIMC1) * (2.0 * covar + SSIMC2)) /
+    ((meana * meana + meanb * meanb + SSIMC1) * (vara + varb + SSIMC2))
+}
+
+// Visualizes the largest channel error of every pixel, black means no error.
+pub fn heatmap(original: &[u8], encoded: &[u8], width: u32, height: u32) -> RgbaImage {
+    assert_eq!(original.len(), (width * height * 4) as usize);
+    assert_eq!(encoded.len(), original.len());
+    let mut res = RgbaImage::new(width, height);
+    for (i, pix) in res.pixels_mut().enumerate() {
+        let a = &original[i * 4..i * 4 + 4];
+        let b = &encoded[i * 4..i * 4 + 4];
+        let error = a.iter().zip(b).map(|(x, y)| x.abs_diff(*y)).max().unwrap_or(0);
+        *pix = heatcolor(error);
+    }
+    res
+}
+
+// Small errors are boosted so they stay visible: black -> blue -> green -> yellow -> red.
+fn heatcolor(error: u8) -> Rgba<u8> {
+    if error == 0 {
+        return Rgba([0, 0, 0, 0xFF]);
+    }
+    let t = ((error as f64 / 64.0).sqrt()).min(1.0) * 4.0;
+    let stops: [[f64; 3]; 5] = [
+        [0.0, 0.0, 0.0],
+        [0.0, 0.0, 255.0],
+        [0.0, 255.0, 0.0],
+        [255.0, 255.0, 0.0],
+        [255.0, 0.0, 0.0],
+    ];
+    let i = (t.floor() as usize).min(3);
+    let frac = t - i as f64;
+    let mut res = [0u8, 0, 0, 0xFF];
+    for c in 0..3 {
+        res[c] = (stops[i][c] + (stops[i + 1][c] - stops[i][c]) * frac).round() as u8;
+    }
+    Rgba(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{BTI, TextureFormats};
+
+    #[test]
+    fn opaque_gray_i8_roundtrip() {
+        let mut bti = BTI::from(RgbaImage::from_fn(16, 16, |x, y| {
+            let v = (y * 16 + x) as u8;
+            Rgba([v, v, v, 0xFF])
+        }));
+        bti.format = TextureFormats::I8;
+        // I8 decodes the intensity into alpha, the game shows it opaque like the original.
+        let report = bti.quality_report();
+        for c in 0..4 {
+            assert!(report.psnr[c] > 50.0, "{}", report);
+            assert!(report.ssim[c] > 0.99, "{}", report);
+        }
+    }
+}
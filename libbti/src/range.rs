@@ -1,4 +1,4 @@
-use std::ops::*;
+use core::ops::*;
 
 
 #[derive(Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -0,0 +1,123 @@
+use alloc::vec::Vec;
+use crate::prelude::{BTI, FilterMode, WrapNodes};
+
+// Samples a texture on the CPU like GX does: the LOD is biased and clamped to the
+// min/max LOD of the header, small LODs magnify with `magfilter`, everything else
+// minifies with `minfilter` and texel coordinates wrap with `wraps`/`wrapt`.
+// Filter values follow the GX API, i.e. NearestMipmapLinear (3) is GX_LIN_MIP_NEAR,
+// bilinear on the nearest level, and LinearMipmapNearest (4) is GX_NEAR_MIP_LIN.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    // RGBA pixels of every level as the game sees them, i.e. after encoding.
+    pub levels: Vec<(u16, u16, Vec<u8>)>,
+    pub wraps: WrapNodes,
+    pub wrapt: WrapNodes,
+    pub minfilter: FilterMode,
+    pub magfilter: FilterMode,
+    pub min_lod: f32,
+    pub max_lod: f32,
+    pub lod_bias: f32,
+}
+
+fn wrap(coord: i32, size: i32, mode: WrapNodes) -> i32 {
+    match mode {
+        WrapNodes::ClampToEdge => coord.clamp(0, size - 1),
+        WrapNodes::Repeat => coord.rem_euclid(size),
+        WrapNodes::MirroredRepeat => {
+            let coord = coord.rem_euclid(size * 2);
+            if coord < size { coord } else { size * 2 - 1 - coord }
+        }
+    }
+}
+
+fn mix(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    [0, 1, 2, 3].map(|c| a[c] + (b[c] - a[c]) * t)
+}
+
+impl Sampler {
+    pub fn new(bti: &BTI) -> Self {
+        let levels = bti.decode_levels().into_iter().enumerate().map(|(level, pixels)| {
+            let (width, height) = crate::mipmap::levelsize(bti.width, bti.height, level as u8);
+            (width, height, pixels)
+        }).collect();
+        Self {
+            levels,
+            wraps: bti.wraps,
+            wrapt: bti.wrapt,
+            minfilter: bti.minfilter,
+            magfilter: bti.magfilter,
+            min_lod: bti.min_lod,
+            max_lod: bti.max_lod,
+            lod_bias: bti.lod_bias,
+        }
+    }
+
+    fn texel(&self, level: usize, x: i32, y: i32) -> [f32; 4] {
+        let (width, height, pixels) = &self.levels[level];
+        let x = wrap(x, *width as i32, self.wraps) as usize;
+        let y = wrap(y, *height as i32, self.wrapt) as usize;
+        let idx = (y * *width as usize + x) * 4;
+        [0, 1, 2, 3].map(|c| pixels[idx + c] as f32)
+    }
+
+    fn filter(&self, level: usize, u: f32, v: f32, linear: bool) -> [f32; 4] {
+        let (width, height, _) = self.levels[level];
+        if !linear {
+            return self.texel(level, (u * width as f32).floor() as i32, (v * height as f32).floor() as i32);
+        }
+        let (x, y) = (u * width as f32 - 0.5, v * height as f32 - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let top = mix(self.texel(level, x0, y0), self.texel(level, x0 + 1, y0), tx);
+        let bottom = mix(self.texel(level, x0, y0 + 1), self.texel(level, x0 + 1, y0 + 1), tx);
+        mix(top, bottom, ty)
+    }
+
+    // `lod` is log2 of the texels covered by one screen pixel, before the bias.
+    pub fn sample(&self, u: f32, v: f32, lod: f32) -> [u8; 4] {
+        let lod = lod + self.lod_bias;
+        let maxlevel = (self.levels.len() - 1) as f32;
+        let clamped = lod.clamp(self.min_lod, self.max_lod.max(self.min_lod)).clamp(0.0, maxlevel);
+        let res = if lod <= 0.0 {
+            self.filter(0, u, v, self.magfilter != FilterMode::Nearest)
+        } else {
+            let (linear, mipmaps, miplinear) = match self.minfilter {
+                FilterMode::Nearest => (false, false, false),
+                FilterMode::Linear => (true, false, false),
+                FilterMode::NearestMipmapNearest => (false, true, false),
+                FilterMode::NearestMipmapLinear => (true, true, false),
+                FilterMode::LinearMipmapNearest => (false, true, true),
+                FilterMode::LinearMipmapLinear => (true, true, true),
+            };
+            match (mipmaps, miplinear) {
+                (false, _) => self.filter(0, u, v, linear),
+                (true, false) => self.filter(clamped.round() as usize, u, v, linear),
+                (true, true) => {
+                    let level = clamped.floor();
+                    let next = (level + 1.0).min(maxlevel);
+                    mix(self.filter(level as usize, u, v, linear), self.filter(next as usize, u, v, linear),
+                        clamped - level)
+                }
+            }
+        };
+        res.map(|x| x.round().clamp(0.0, 255.0) as u8)
+    }
+
+    // Renders the texture repeated `tilesu` x `tilesv` times into width x height RGBA
+    // pixels, the LOD follows from how much the texture is scaled down.
+    pub fn render(&self, width: u32, height: u32, tilesu: f32, tilesv: f32) -> Vec<u8> {
+        let (texwidth, texheight, _) = self.levels[0];
+        let scale = (tilesu * texwidth as f32 / width as f32).max(tilesv * texheight as f32 / height as f32);
+        let lod = scale.log2();
+        let mut res = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let u = (x as f32 + 0.5) / width as f32 * tilesu;
+                let v = (y as f32 + 0.5) / height as f32 * tilesv;
+                res.extend(self.sample(u, v, lod));
+            }
+        }
+        res
+    }
+}
@@ -0,0 +1,213 @@
+use crate::prelude::{BTI, TextureFormats, PaletteFormats, Palette, QualityReport, alphasetting};
+use crate::quality;
+
+// Alpha values which survive the 3 bit alpha of RGB5A3 unchanged.
+const ALPHA3BIT: [u8; 8] = [0x00, 0x24, 0x49, 0x6D, 0x92, 0xB6, 0xDB, 0xFF];
+
+const DIRECTFORMATS: [TextureFormats; 8] = [
+    TextureFormats::I4, TextureFormats::IA4, TextureFormats::I8, TextureFormats::IA8,
+    TextureFormats::RGB565, TextureFormats::RGB5A3, TextureFormats::RGBA32, TextureFormats::CMPR
+];
+const PALETTEFORMATS: [PaletteFormats; 3] = [
+    PaletteFormats::IA8, PaletteFormats::RGB565, PaletteFormats::RGB5A3
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ImageAnalysis {
+    // Capped at 257, anything above that does not fit into a C8 palette anyway.
+    pub uniquecolors: usize,
+    // 0 (opaque), 1, 3, 4 or 8 bits needed to store the alpha channel losslessly.
+    pub alphadepth: u8,
+    pub grayscale: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub format: TextureFormats,
+    pub paletteformat: Option<PaletteFormats>,
+    // Encoded image data plus palette, in bytes.
+    pub size: usize,
+    pub report: QualityReport,
+}
+
+#[derive(Debug, Clone)]
+pub struct FormatChoice {
+    pub format: TextureFormats,
+    pub paletteformat: Option<PaletteFormats>,
+    pub palette: Option<Palette>,
+    pub analysis: ImageAnalysis,
+    pub candidates: Vec<Candidate>,
+    pub reason: String,
+}
+
+impl FormatChoice {
+    pub fn apply(&self, bti: &mut BTI) {
+        bti.format = self.format;
+        match (self.paletteformat, &self.palette) {
+            (Some(paletteformat), Some(palette)) => {
+                bti.paletteformat = paletteformat;
+                bti.palettesenabled = true;
+                bti.palettecount = palette.count() as u16;
+                bti.imagepalette = palette.clone();
+            },
+            _ => {
+                bti.palettesenabled = false;
+                bti.palettecount = 0;
+                bti.imagepalette = Palette::default();
+            }
+        }
+        bti.alphasetting = alphasetting(bti.format, bti.paletteformat, &bti.rgbaimagedata) as u8;
+    }
+}
+
+// Works on RGBA pixels.
+pub fn analyze(data: &[u8]) -> ImageAnalysis {
+    let mut colors: Vec<[u8; 4]> = vec![];
+    let mut grayscale = true;
+    let (mut opaque, mut onebit, mut threebit, mut fourbit) = (true, true, true, true);
+    for pix in data.chunks_exact(4) {
+        let (r, g, b, a) = (pix[0], pix[1], pix[2], pix[3]);
+        if grayscale && (r != g || g != b) {
+            grayscale = false;
+        }
+        opaque &= a == 0xFF;
+        onebit &= a == 0x00 || a == 0xFF;
+        threebit &= ALPHA3BIT.contains(&a);
+        fourbit &= a % 0x11 == 0;
+        if colors.len() <= 256 {
+            let color = [r, g, b, a];
+            if !colors.contains(&color) {
+                colors.push(color);
+            }
+        }
+    }
+    let alphadepth = if opaque {
+        0
+    } else if onebit {
+        1
+    } else if threebit {
+        3
+    } else if fourbit {
+        4
+    } else {
+        8
+    };
+    ImageAnalysis { uniquecolors: colors.len(), alphadepth, grayscale }
+}
+
+fn evaluate(bti: &BTI) -> Candidate {
+    let data = bti.encodedata();
+    let size = data.len() + bti.imagepalette.palettedata.len();
+    // The game alpha, formats without alpha must not be judged by what their decoders fill in.
+    let decoded = bti.decode_for_game(&data);
+    let report = quality::compare(&bti.rgbaimagedata, &decoded, bti.width.into(), bti.height.into());
+    let paletteformat = match bti.format {
+        TextureFormats::C4 | TextureFormats::C8 => Some(bti.paletteformat),
+        _ => None
+    };
+    Candidate { format: bti.format, paletteformat, size, report }
+}
+
+fn describe(analysis: &ImageAnalysis) -> String {
+    let colors = match analysis.uniquecolors {
+        257 => "more than 256 colors".to_string(),
+        n => format!("{} colors", n)
+    };
+    let alpha = match analysis.alphadepth {
+        0 => "no alpha".to_string(),
+        n => format!("{} bit alpha", n)
+    };
+    let chroma = if analysis.grayscale { "grayscale" } else { "color" };
+    format!("{}, {}, {}", chroma, colors, alpha)
+}
+
+fn candidatename(candidate: &Candidate) -> String {
+    match candidate.paletteformat {
+        Some(paletteformat) => format!("{:?} ({:?} palette)", candidate.format, paletteformat),
+        None => format!("{:?}", candidate.format)
+    }
+}
+
+// Encodes the texture in every format that can hold it and picks the smallest one
+// whose worst channel PSNR is at least `minpsnr`. RGBA32 is lossless, so there is
+// always a format that qualifies.
+pub fn selectformat(bti: &BTI, minpsnr: f64) -> FormatChoice {
+    let analysis = analyze(&bti.rgbaimagedata);
+    let mut candidates = vec![];
+    let mut palettes: Vec<Option<Palette>> = vec![];
+    let mut base = bti.clone();
+    base.palettesenabled = false;
+    base.palettecount = 0;
+    base.imagepalette = Palette::default();
+    for format in DIRECTFORMATS {
+        let mut candidate = base.clone();
+        candidate.format = format;
+        candidates.push(evaluate(&candidate));
+        palettes.push(None);
+    }
+    for format in [TextureFormats::C4, TextureFormats::C8] {
+        let maxcolors = if format == TextureFormats::C4 { 16 } else { 256 };
+        if analysis.uniquecolors > maxcolors {
+            continue;
+        }
+        for paletteformat in PALETTEFORMATS {
+            let mut candidate = base.clone();
+            candidate.format = format;
+            candidate.paletteformat = paletteformat;
+            if !candidate.generate_palette() {
+                continue;
+            }
+            candidates.push(evaluate(&candidate));
+            palettes.push(Some(candidate.imagepalette));
+        }
+    }
+    let smallest = candidates.iter().enumerate()
+    .filter(|(_, c)| c.report.minpsnr() >= minpsnr)
+    .min_by(|(_, a), (_, b)| a.size.cmp(&b.size)
+        .then(b.report.minpsnr().total_cmp(&a.report.minpsnr())))
+    .map(|(i, _)| i);
+    let (chosen, passed) = match smallest {
+        Some(i) => (i, true),
+        None => (candidates.iter().enumerate()
+            .max_by(|(_, a), (_, b)| a.report.minpsnr().total_cmp(&b.report.minpsnr()))
+            .map(|(i, _)| i).unwrap(), false)
+    };
+    let candidate = &candidates[chosen];
+    let reason = if passed {
+        format!("{}: {} is the smallest format ({} bytes) with a minimum PSNR of {:.2} dB >= {:.2} dB",
+        describe(&analysis), candidatename(candidate), candidate.size, candidate.report.minpsnr(), minpsnr)
+    } else {
+        format!("{}: no format reaches {:.2} dB, using the most accurate one", describe(&analysis), minpsnr)
+    };
+    FormatChoice {
+        format: candidate.format,
+        paletteformat: candidate.paletteformat,
+        palette: palettes[chosen].take(),
+        analysis,
+        reason,
+        candidates,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    fn gray(f: impl Fn(u32, u32) -> u8) -> BTI {
+        BTI::from(RgbaImage::from_fn(16, 16, |x, y| {
+            let v = f(x, y);
+            Rgba([v, v, v, 0xFF])
+        }))
+    }
+
+    #[test]
+    fn intensity_wins_for_gray() {
+        // 256 gray levels only survive I8, IA8 and RGBA32 unchanged, I8 is the smallest.
+        let choice = selectformat(&gray(|x, y| (y * 16 + x) as u8), 45.0);
+        assert_eq!(choice.format, TextureFormats::I8, "{}", choice.reason);
+        // 16 levels fit I4 exactly, CMPR has the same size but loses some of them.
+        let choice = selectformat(&gray(|x, y| ((x + y) % 16 * 17) as u8), 45.0);
+        assert_eq!(choice.format, TextureFormats::I4, "{}", choice.reason);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::prelude::{Anisotropy, BTI, FilterMode, PaletteFormats, TextureFormats, WrapNodes};
+
+// The header fields of a BTI that aren't derived from the pixel data, i.e. everything
+// besides the size, palette count and offsets. Missing fields deserialize to the defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct BtiSettings {
+    pub format: TextureFormats,
+    pub alphasetting: u8,
+    pub wraps: WrapNodes,
+    pub wrapt: WrapNodes,
+    pub paletteformat: PaletteFormats,
+    pub mipmaps_enabled: bool,
+    pub edge_lod: bool,
+    pub bias_clamp: bool,
+    pub max_aniso: Anisotropy,
+    pub minfilter: FilterMode,
+    pub magfilter: FilterMode,
+    pub min_lod: f32,
+    pub max_lod: f32,
+    pub mipmapcount: u8,
+    pub unknown3: u8,
+    pub lod_bias: f32,
+}
+
+impl BtiSettings {
+    // Copies the settings into `bti`, a C4/C8 palette still has to be generated.
+    pub fn apply(&self, bti: &mut BTI) {
+        bti.format = self.format;
+        bti.alphasetting = self.alphasetting;
+        bti.wraps = self.wraps;
+        bti.wrapt = self.wrapt;
+        bti.paletteformat = self.paletteformat;
+        bti.mipmaps_enabled = self.mipmaps_enabled;
+        bti.edge_lod = self.edge_lod;
+        bti.bias_clamp = self.bias_clamp;
+        bti.max_aniso = self.max_aniso;
+        bti.minfilter = self.minfilter;
+        bti.magfilter = self.magfilter;
+        bti.min_lod = self.min_lod;
+        bti.max_lod = self.max_lod;
+        bti.mipmapcount = self.mipmapcount;
+        bti.unknown3 = self.unknown3;
+        bti.lod_bias = self.lod_bias;
+    }
+}
+
+impl From<&BTI> for BtiSettings {
+    fn from(bti: &BTI) -> Self {
+        Self {
+            format: bti.format,
+            alphasetting: bti.alphasetting,
+            wraps: bti.wraps,
+            wrapt: bti.wrapt,
+            paletteformat: bti.paletteformat,
+            mipmaps_enabled: bti.mipmaps_enabled,
+            edge_lod: bti.edge_lod,
+            bias_clamp: bti.bias_clamp,
+            max_aniso: bti.max_aniso,
+            minfilter: bti.minfilter,
+            magfilter: bti.magfilter,
+            min_lod: bti.min_lod,
+            max_lod: bti.max_lod,
+            mipmapcount: bti.mipmapcount,
+            unknown3: bti.unknown3,
+            lod_bias: bti.lod_bias,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn apply_roundtrip() {
+        let settings = BtiSettings {
+            format: TextureFormats::C8,
+            alphasetting: 2,
+            wraps: WrapNodes::Repeat,
+            wrapt: WrapNodes::MirroredRepeat,
+            paletteformat: PaletteFormats::RGB5A3,
+            mipmaps_enabled: true,
+            edge_lod: true,
+            bias_clamp: true,
+            max_aniso: Anisotropy::Four,
+            minfilter: FilterMode::LinearMipmapLinear,
+            magfilter: FilterMode::Linear,
+            min_lod: 0.5,
+            max_lod: 3.0,
+            mipmapcount: 4,
+            unknown3: 7,
+            lod_bias: -1.25,
+        };
+        let mut bti = BTI { width: 8, height: 4, palettecount: 3, rgbaimagedata: vec![0x7F; 8 * 4 * 4], ..Default::default() };
+        settings.apply(&mut bti);
+        assert_eq!(BtiSettings::from(&bti), settings);
+        // The size, palette count and pixels belong to the texture, not the settings.
+        assert_eq!((bti.width, bti.height, bti.palettecount), (8, 4, 3));
+        assert_eq!(bti.rgbaimagedata, vec![0x7F; 8 * 4 * 4]);
+        BtiSettings::default().apply(&mut bti);
+        assert_eq!(BtiSettings::from(&bti), BtiSettings::default());
+    }
+}
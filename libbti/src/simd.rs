@@ -0,0 +1,209 @@
+// Vectorized pixel expansion used by the decoders. SSE2 is part of every x86_64 cpu,
+// other targets use the scalar versions which give the exact same results.
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+use crate::decoders::{rgb565torgba8, rgb5a3torgba8};
+use crate::enums::PixelLayout;
+
+// Expands four big endian RGB565 pixels (8 bytes) into four pixels (16 bytes).
+pub fn rgb565x4(src: &[u8], dst: &mut [u8], layout: PixelLayout) {
+    assert!(src.len() >= 8 && dst.len() >= 16);
+    #[cfg(target_arch = "x86_64")]
+    unsafe { rgb565x4sse2(src, dst, layout) }
+    #[cfg(not(target_arch = "x86_64"))]
+    rgb565x4scalar(src, dst, layout)
+}
+
+// Same as rgb565x4 for RGB5A3 pixels.
+pub fn rgb5a3x4(src: &[u8], dst: &mut [u8], layout: PixelLayout) {
+    assert!(src.len() >= 8 && dst.len() >= 16);
+    #[cfg(target_arch = "x86_64")]
+    unsafe { rgb5a3x4sse2(src, dst, layout) }
+    #[cfg(not(target_arch = "x86_64"))]
+    rgb5a3x4scalar(src, dst, layout)
+}
+
+// The four RGBA colors of a CMPR sub-block.
+pub fn cmprpalette(color1: u16, color2: u16) -> [[u8; 4]; 4] {
+    #[cfg(target_arch = "x86_64")]
+    unsafe { cmprpalettesse2(color1, color2) }
+    #[cfg(not(target_arch = "x86_64"))]
+    cmprpalettescalar(color1, color2)
+}
+
+pub fn rgb565x4scalar(src: &[u8], dst: &mut [u8], layout: PixelLayout) {
+    for i in 0..4 {
+        let mut color = [0u8; 4];
+        rgb565torgba8(u16::from_be_bytes([src[2 * i], src[2 * i + 1]]), &mut color, 0);
+        dst[i * 4..i * 4 + 4].copy_from_slice(&layout.arrange(color));
+    }
+}
+
+pub fn rgb5a3x4scalar(src: &[u8], dst: &mut [u8], layout: PixelLayout) {
+    for i in 0..4 {
+        let mut color = [0u8; 4];
+        rgb5a3torgba8(u16::from_be_bytes([src[2 * i], src[2 * i + 1]]), &mut color, 0);
+        dst[i * 4..i * 4 + 4].copy_from_slice(&layout.arrange(color));
+    }
+}
+
+pub fn cmprpalettescalar(color1: u16, color2: u16) -> [[u8; 4]; 4] {
+    let mut colortable = [[0u8; 4]; 4];
+    rgb565torgba8(color1, &mut colortable[0], 0);
+    rgb565torgba8(color2, &mut colortable[1], 0);
+    let (c1, c2) = (colortable[0], colortable[1]);
+    let mix = |w1: u16, w2: u16, div: u16| {
+        let mut res = [0xFFu8; 4];
+        for ((dst, a), b) in res.iter_mut().zip(c1).zip(c2).take(3) {
+            *dst = ((w1 * a as u16 + w2 * b as u16) / div) as u8;
+        }
+        res
+    };
+    if color1 > color2 {
+        colortable[2] = mix(2, 1, 3);
+        colortable[3] = mix(1, 2, 3);
+    } else {
+        // Three color mode, the last entry is fully transparent.
+        colortable[2] = mix(1, 1, 2);
+    }
+    colortable
+}
+
+// Loads four big endian u16 into the low 16 bit lanes.
+#[cfg(target_arch = "x86_64")]
+unsafe fn load4be(src: &[u8]) -> __m128i {
+    let v = _mm_loadl_epi64(src.as_ptr() as *const __m128i);
+    _mm_or_si128(_mm_slli_epi16(v, 8), _mm_srli_epi16(v, 8))
+}
+
+// Widens 5 and 6 bit values in 16 bit lanes to 8 bits the same way the scalar code does.
+#[cfg(target_arch = "x86_64")]
+unsafe fn expand5(v: __m128i) -> __m128i {
+    _mm_or_si128(_mm_slli_epi16(v, 3), _mm_srli_epi16(v, 2))
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn expand6(v: __m128i) -> __m128i {
+    _mm_or_si128(_mm_slli_epi16(v, 2), _mm_srli_epi16(v, 4))
+}
+
+// Interleaves 8 bit values held in 16 bit lanes into pixels and stores four of them.
+#[cfg(target_arch = "x86_64")]
+unsafe fn store4(r: __m128i, g: __m128i, b: __m128i, a: __m128i, dst: &mut [u8], layout: PixelLayout) {
+    let (first, third) = match layout {
+        PixelLayout::Rgba => (r, b),
+        PixelLayout::Bgra => (b, r),
+    };
+    let low = _mm_or_si128(first, _mm_slli_epi16(g, 8));
+    let high = _mm_or_si128(third, _mm_slli_epi16(a, 8));
+    _mm_storeu_si128(dst.as_mut_ptr() as *mut __m128i, _mm_unpacklo_epi16(low, high));
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn rgb565x4sse2(src: &[u8], dst: &mut [u8], layout: PixelLayout) {
+    let v = load4be(src);
+    let r = expand5(_mm_srli_epi16(v, 11));
+    let g = expand6(_mm_and_si128(_mm_srli_epi16(v, 5), _mm_set1_epi16(0x3F)));
+    let b = expand5(_mm_and_si128(v, _mm_set1_epi16(0x1F)));
+    store4(r, g, b, _mm_set1_epi16(0xFF), dst, layout);
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn rgb5a3x4sse2(src: &[u8], dst: &mut [u8], layout: PixelLayout) {
+    let v = load4be(src);
+    let (mask5, mask4) = (_mm_set1_epi16(0x1F), _mm_set1_epi16(0xF));
+    // Pixels with the top bit set are RGB555 and opaque.
+    let opaque = _mm_srai_epi16(v, 15);
+    let select = |a: __m128i, b: __m128i| _mm_or_si128(_mm_and_si128(opaque, a), _mm_andnot_si128(opaque, b));
+    let expand4 = |v: __m128i| _mm_or_si128(_mm_slli_epi16(v, 4), v);
+    let r = select(expand5(_mm_and_si128(_mm_srli_epi16(v, 10), mask5)),
+        expand4(_mm_and_si128(_mm_srli_epi16(v, 8), mask4)));
+    let g = select(expand5(_mm_and_si128(_mm_srli_epi16(v, 5), mask5)),
+        expand4(_mm_and_si128(_mm_srli_epi16(v, 4), mask4)));
+    let b = select(expand5(_mm_and_si128(v, mask5)), expand4(_mm_and_si128(v, mask4)));
+    let a3 = _mm_and_si128(_mm_srli_epi16(v, 12), _mm_set1_epi16(0x7));
+    let a = _mm_or_si128(_mm_or_si128(_mm_slli_epi16(a3, 5), _mm_slli_epi16(a3, 2)), _mm_srli_epi16(a3, 1));
+    let a = select(_mm_set1_epi16(0xFF), a);
+    store4(r, g, b, a, dst, layout);
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn cmprpalettesse2(color1: u16, color2: u16) -> [[u8; 4]; 4] {
+    let mut res = [[0u8; 4]; 4];
+    let colors = [color1.to_be_bytes(), color2.to_be_bytes(), [0; 2], [0; 2]].concat();
+    let mut ends = [0u8; 16];
+    rgb565x4sse2(&colors, &mut ends, PixelLayout::Rgba);
+    // Both colors as 16 bit lanes, once as c1 c2 and once swapped as c2 c1.
+    let c = _mm_unpacklo_epi8(_mm_loadl_epi64(ends.as_ptr() as *const __m128i), _mm_setzero_si128());
+    let swapped = _mm_shuffle_epi32(c, 0b01_00_11_10);
+    let mixed = if color1 > color2 {
+        // x * 21846 >> 16 is x / 3 for every x below 32768.
+        let sum = _mm_add_epi16(_mm_add_epi16(c, c), swapped);
+        _mm_mulhi_epu16(sum, _mm_set1_epi16(21846))
+    } else {
+        _mm_srli_epi16(_mm_add_epi16(c, swapped), 1)
+    };
+    let mut packed = [0u8; 16];
+    _mm_storeu_si128(packed.as_mut_ptr() as *mut __m128i, _mm_packus_epi16(mixed, mixed));
+    res[0].copy_from_slice(&ends[0..4]);
+    res[1].copy_from_slice(&ends[4..8]);
+    res[2] = [packed[0], packed[1], packed[2], 0xFF];
+    if color1 > color2 {
+        res[3] = [packed[4], packed[5], packed[6], 0xFF];
+    }
+    res
+}
+
+#[cfg(all(test, target_arch = "x86_64"))]
+mod tests {
+    use super::*;
+
+    const LAYOUTS: [PixelLayout; 2] = [PixelLayout::Rgba, PixelLayout::Bgra];
+
+    // Every u16 value, four at a time.
+    fn allpixels() -> impl Iterator<Item = [u8; 8]> {
+        (0..=u16::MAX).step_by(4).map(|v| {
+            let mut src = [0u8; 8];
+            for i in 0..4 {
+                src[2 * i..2 * i + 2].copy_from_slice(&(v + i as u16).to_be_bytes());
+            }
+            src
+        })
+    }
+
+    #[test]
+    fn rgb565x4_matches_scalar() {
+        for layout in LAYOUTS {
+            for src in allpixels() {
+                let (mut simd, mut scalar) = ([0u8; 16], [0u8; 16]);
+                unsafe { rgb565x4sse2(&src, &mut simd, layout) };
+                rgb565x4scalar(&src, &mut scalar, layout);
+                assert_eq!(simd, scalar, "{:02x?} {:?}", src, layout);
+            }
+        }
+    }
+
+    #[test]
+    fn rgb5a3x4_matches_scalar() {
+        for layout in LAYOUTS {
+            for src in allpixels() {
+                let (mut simd, mut scalar) = ([0u8; 16], [0u8; 16]);
+                unsafe { rgb5a3x4sse2(&src, &mut simd, layout) };
+                rgb5a3x4scalar(&src, &mut scalar, layout);
+                assert_eq!(simd, scalar, "{:02x?} {:?}", src, layout);
+            }
+        }
+    }
+
+    // All first colors against the extremes, itself and a few unrelated seconds, which
+    // covers both modes and equal endpoints without going through all 2^32 pairs.
+    #[test]
+    fn cmprpalette_matches_scalar() {
+        for color1 in 0..=u16::MAX {
+            for color2 in [0, u16::MAX, color1, color1 ^ 0x8410, color1.rotate_left(5), color1.wrapping_mul(40503)] {
+                let simd = unsafe { cmprpalettesse2(color1, color2) };
+                assert_eq!(simd, cmprpalettescalar(color1, color2), "{:04x} {:04x}", color1, color2);
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::io::Cursor;
+use std::ops::Range;
+use crate::prelude::{BTI, HEADERSIZE, Palette, PixelLayout, TextureFormats};
+use crate::{decoders, mipmap};
+
+// A BTI inside of a byte slice. Only the header is parsed up front, nothing is copied
+// until the pixels are decoded. The offsets are checked while parsing, so the raw
+// level and palette data can be handed out without further checks.
+#[derive(Debug, Clone)]
+pub struct BtiView<'a> {
+    // Header fields only, the palette and pixel data are empty.
+    pub header: BTI,
+    pub data: &'a [u8],
+}
+
+impl<'a> BtiView<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, String> {
+        if data.len() < HEADERSIZE as usize {
+            return Err(format!("{} bytes are too short for a BTI header", data.len()));
+        }
+        let header = BTI::try_read_header(&mut Cursor::new(data)).map_err(|e| match e.root_cause() {
+            binrw::Error::NoVariantMatch { pos } => format!("unknown value 0x{:02x} at header offset 0x{:x}",
+                data[*pos as usize], pos),
+            e => format!("unreadable header: {}", e)
+        })?;
+        let res = Self { header, data };
+        if res.header.palettecount > 0 {
+            res.checkrange("palette", res.paletterange())?;
+        }
+        for level in 0..res.levels() {
+            res.checkrange(&format!("mip level {}", level), res.levelrange(level))?;
+        }
+        Ok(res)
+    }
+
+    fn checkrange(&self, what: &str, range: Range<usize>) -> Result<(), String> {
+        if range.start < HEADERSIZE as usize || range.end > self.data.len() {
+            return Err(format!("{} at 0x{:x}..0x{:x} is outside of the {} byte file", what, range.start,
+            range.end, self.data.len()));
+        }
+        Ok(())
+    }
+
+    // Number of image levels stored, a count of 0 is treated as 1 like the games do.
+    pub fn levels(&self) -> u8 {
+        self.header.mipmapcount.max(1)
+    }
+
+    pub fn levelsize(&self, level: u8) -> (u16, u16) {
+        mipmap::levelsize(self.header.width, self.header.height, level)
+    }
+
+    fn paletterange(&self) -> Range<usize> {
+        let start = self.header.palettedataoffset.max(0) as usize;
+        start..start + self.header.palettecount as usize * 2
+    }
+
+    fn levelrange(&self, level: u8) -> Range<usize> {
+        let size = |level| {
+            let (width, height) = self.levelsize(level);
+            decoders::encodedsize(self.header.format, width, height)
+        };
+        let start = self.header.imagedataoffset.max(0) as usize + (0..level).map(size).sum::<usize>();
+        start..start + size(level)
+    }
+
+    // The encoded palette entries, empty without palette.
+    pub fn palette_data(&self) -> &'a [u8] {
+        match self.header.palettecount {
+            0 => &[],
+            _ => &self.data[self.paletterange()]
+        }
+    }
+
+    // The encoded bytes of one level, e.g. to copy a texture without re-encoding it.
+    pub fn level_data(&self, level: u8) -> &'a [u8] {
+        assert!(level < self.levels(), "Level {} of {} requested.", level, self.levels());
+        &self.data[self.levelrange(level)]
+    }
+
+    // The encoded bytes of all levels.
+    pub fn image_data(&self) -> &'a [u8] {
+        let start = self.levelrange(0).start;
+        &self.data[start..self.levelrange(self.levels() - 1).end]
+    }
+
+    pub fn palette(&self) -> Palette {
+        Palette { palettedata: self.palette_data().to_vec() }
+    }
+
+    // RGBA pixels of one level.
+    pub fn decode_level(&self, level: u8) -> Result<Vec<u8>, String> {
+        self.decode_level_layout(level, PixelLayout::Rgba)
+    }
+
+    pub fn decode_level_layout(&self, level: u8, layout: PixelLayout) -> Result<Vec<u8>, String> {
+        if level >= self.levels() {
+            return Err(format!("Level {} requested, the texture has {}", level, self.levels()));
+        }
+        let format = self.header.format;
+        let (width, height) = self.levelsize(level);
+        let src = self.level_data(level);
+        let mut dst = vec![0u8; width as usize * height as usize * 4];
+        match format {
+            TextureFormats::C4 | TextureFormats::C8 => decoders::decode_indexed_into_layout(format, src,
+                width, height, self.palette_data(), self.header.paletteformat, &mut dst, layout),
+            TextureFormats::C14X2 => return Err("C14X2 textures are not supported".to_string()),
+            _ => decoders::decode_into_layout(format, src, width, height, &mut dst, layout)
+        }
+        Ok(dst)
+    }
+
+    // Same result as BTI::read on the data, panics on C14X2 textures.
+    pub fn decode(&self) -> BTI {
+        self.try_decode().unwrap()
+    }
+
+    // Like `decode`, but formats that can't be decoded are errors.
+    pub fn try_decode(&self) -> Result<BTI, String> {
+        let mut res = self.header.clone();
+        if res.palettecount > 0 {
+            res.imagepalette = self.palette();
+        }
+        res.rgbaimagedata = self.decode_level(0)?;
+        res.keep_raw(self.image_data().to_vec());
+        Ok(res)
+    }
+}
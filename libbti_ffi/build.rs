@@ -0,0 +1,24 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// Generates the C header into OUT_DIR. include/libbti.h is committed so C users don't need
+// cbindgen, run the build with LIBBTI_UPDATE_HEADER=1 to copy a changed header over it.
+fn main() {
+    let dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("libbti.h");
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=include/libbti.h");
+    println!("cargo:rerun-if-env-changed=LIBBTI_UPDATE_HEADER");
+    cbindgen::generate(&dir).expect("Unable to generate the C header.").write_to_file(&out);
+    let committed = Path::new(&dir).join("include").join("libbti.h");
+    if fs::read(&out).ok() == fs::read(&committed).ok() {
+        return;
+    }
+    if env::var_os("LIBBTI_UPDATE_HEADER").is_some() {
+        fs::copy(&out, &committed).unwrap();
+    } else {
+        println!("cargo:warning=include/libbti.h is out of date, rebuild with LIBBTI_UPDATE_HEADER=1");
+    }
+}
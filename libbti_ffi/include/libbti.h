@@ -0,0 +1,154 @@
+/* Generated by cbindgen from libbti_ffi/src/lib.rs, do not edit. */
+
+#ifndef LIBBTI_H
+#define LIBBTI_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Result of every libbti function.
+ */
+typedef enum BtiError {
+  BTI_ERROR_OK = 0,
+  /**
+   * A required pointer argument was NULL.
+   */
+  BTI_ERROR_NULL_POINTER = 1,
+  /**
+   * The data is not a valid BTI file.
+   */
+  BTI_ERROR_INVALID_DATA = 2,
+  /**
+   * The texture uses a format libbti can't decode or encode (C14X2).
+   */
+  BTI_ERROR_UNSUPPORTED_FORMAT = 3,
+  /**
+   * An argument is out of range, e.g. an unknown enum value or a missing mip level.
+   */
+  BTI_ERROR_INVALID_ARGUMENT = 4,
+  /**
+   * The pixels have more colors than the C4/C8 palette can hold.
+   */
+  BTI_ERROR_TOO_MANY_COLORS = 5,
+  /**
+   * libbti panicked, this is a bug.
+   */
+  BTI_ERROR_PANIC = 6,
+} BtiError;
+
+/**
+ * A parsed BTI file, created by bti_parse and released with bti_free.
+ */
+typedef struct BtiTexture BtiTexture;
+
+/**
+ * Header fields of a texture. The enums use the values stored in the file.
+ */
+typedef struct BtiInfo {
+  uint16_t width;
+  uint16_t height;
+  uint8_t format;
+  uint8_t alphasetting;
+  uint8_t wraps;
+  uint8_t wrapt;
+  uint8_t paletteformat;
+  uint16_t palettecount;
+  uint8_t minfilter;
+  uint8_t magfilter;
+  /**
+   * Number of levels stored, at least 1.
+   */
+  uint8_t mipmapcount;
+  bool mipmaps_enabled;
+  bool edge_lod;
+  bool bias_clamp;
+  uint8_t max_aniso;
+  /**
+   * LOD range and bias in levels, the file stores them in steps of 1/8 and 1/100.
+   */
+  float min_lod;
+  float max_lod;
+  float lod_bias;
+} BtiInfo;
+
+/**
+ * Settings for bti_encode, start from bti_encode_options_default.
+ */
+typedef struct BtiEncodeOptions {
+  /**
+   * Texture format value, or -1 to pick one from the pixels.
+   */
+  int32_t format;
+  /**
+   * Only used with the palette formats C4, C8 and C14X2.
+   */
+  uint8_t paletteformat;
+  uint8_t wraps;
+  uint8_t wrapt;
+  uint8_t minfilter;
+  uint8_t magfilter;
+  /**
+   * Number of levels including the full size one, clamped to what the size allows.
+   */
+  uint8_t mipmaps;
+} BtiEncodeOptions;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Static, NUL terminated description of an error code. Takes an int so values that aren't
+ * a BtiError are safe to pass, they get a generic message.
+ */
+const char *bti_error_message(int32_t error);
+
+/**
+ * Parses `len` bytes of a BTI file, the data is copied.
+ */
+enum BtiError bti_parse(const uint8_t *data, size_t len, struct BtiTexture **out);
+
+/**
+ * Releases a texture from bti_parse, NULL is ignored.
+ */
+void bti_free(struct BtiTexture *texture);
+
+enum BtiError bti_info(const struct BtiTexture *texture, struct BtiInfo *out);
+
+/**
+ * Decodes one level to RGBA8 (or BGRA8 if `bgra` is true). The buffer of
+ * width * height * 4 bytes has to be released with bti_free_buffer.
+ */
+enum BtiError bti_decode_level(const struct BtiTexture *texture,
+                               uint8_t level,
+                               bool bgra,
+                               uint8_t **out,
+                               size_t *outlen);
+
+struct BtiEncodeOptions bti_encode_options_default(void);
+
+/**
+ * Encodes width * height RGBA8 pixels into a complete BTI file. `options` may be NULL
+ * for the defaults. The buffer has to be released with bti_free_buffer.
+ */
+enum BtiError bti_encode(const uint8_t *pixels,
+                         uint16_t width,
+                         uint16_t height,
+                         const struct BtiEncodeOptions *options,
+                         uint8_t **out,
+                         size_t *outlen);
+
+/**
+ * Releases a buffer from bti_decode_level or bti_encode, NULL is ignored.
+ */
+void bti_free_buffer(uint8_t *data, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* LIBBTI_H */
@@ -0,0 +1,256 @@
+// C API over libbti. Every function returns a BtiError, results are passed back through
+// out pointers. Panics are caught at the boundary and reported as BTI_ERROR_PANIC.
+// The `///` comments end up in include/libbti.h.
+#![allow(clippy::missing_safety_doc)]
+use std::ffi::c_char;
+use std::io::Cursor;
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+use libbti::prelude::*;
+use libbti::prelude::binrw::{BinRead, BinReaderExt};
+
+/// Result of every libbti function.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BtiError {
+    Ok = 0,
+    /// A required pointer argument was NULL.
+    NullPointer = 1,
+    /// The data is not a valid BTI file.
+    InvalidData = 2,
+    /// The texture uses a format libbti can't decode or encode (C14X2).
+    UnsupportedFormat = 3,
+    /// An argument is out of range, e.g. an unknown enum value or a missing mip level.
+    InvalidArgument = 4,
+    /// The pixels have more colors than the C4/C8 palette can hold.
+    TooManyColors = 5,
+    /// libbti panicked, this is a bug.
+    Panic = 6,
+}
+
+/// A parsed BTI file, created by bti_parse and released with bti_free.
+pub struct BtiTexture {
+    data: Vec<u8>,
+    bti: BTI,
+}
+
+/// Header fields of a texture. The enums use the values stored in the file.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BtiInfo {
+    pub width: u16,
+    pub height: u16,
+    pub format: u8,
+    pub alphasetting: u8,
+    pub wraps: u8,
+    pub wrapt: u8,
+    pub paletteformat: u8,
+    pub palettecount: u16,
+    pub minfilter: u8,
+    pub magfilter: u8,
+    /// Number of levels stored, at least 1.
+    pub mipmapcount: u8,
+    pub mipmaps_enabled: bool,
+    pub edge_lod: bool,
+    pub bias_clamp: bool,
+    pub max_aniso: u8,
+    /// LOD range and bias in levels, the file stores them in steps of 1/8 and 1/100.
+    pub min_lod: f32,
+    pub max_lod: f32,
+    pub lod_bias: f32,
+}
+
+/// Settings for bti_encode, start from bti_encode_options_default.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BtiEncodeOptions {
+    /// Texture format value, or -1 to pick one from the pixels.
+    pub format: i32,
+    /// Only used with the palette formats C4, C8 and C14X2.
+    pub paletteformat: u8,
+    pub wraps: u8,
+    pub wrapt: u8,
+    pub minfilter: u8,
+    pub magfilter: u8,
+    /// Number of levels including the full size one, clamped to what the size allows.
+    pub mipmaps: u8,
+}
+
+// Catches panics so they never unwind into C.
+fn guard(f: impl FnOnce() -> Result<(), BtiError>) -> BtiError {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => BtiError::Ok,
+        Ok(Err(e)) => e,
+        Err(_) => BtiError::Panic,
+    }
+}
+
+// Reads an enum from its file value, unknown values are errors instead of panics.
+fn enumvalue<T: BinRead<Args = ()>>(value: u8) -> Result<T, BtiError> {
+    Cursor::new([value]).read_ne().map_err(|_| BtiError::InvalidArgument)
+}
+
+// Everything but the two errors with their own code is a bad argument.
+fn builderror(e: BuildError) -> BtiError {
+    match e {
+        BuildError::TooManyColors(_) => BtiError::TooManyColors,
+        BuildError::UnsupportedFormat(_) => BtiError::UnsupportedFormat,
+        _ => BtiError::InvalidArgument,
+    }
+}
+
+unsafe fn texture<'a>(texture: *const BtiTexture) -> Result<&'a BtiTexture, BtiError> {
+    texture.as_ref().ok_or(BtiError::NullPointer)
+}
+
+// Hands a buffer over to C, it has to come back through bti_free_buffer.
+unsafe fn givebuffer(data: Vec<u8>, out: *mut *mut u8, outlen: *mut usize) {
+    let data = Box::into_raw(data.into_boxed_slice());
+    *outlen = data.len();
+    *out = data as *mut u8;
+}
+
+/// Static, NUL terminated description of an error code. Takes an int so values that aren't
+/// a BtiError are safe to pass, they get a generic message.
+#[no_mangle]
+pub extern "C" fn bti_error_message(error: i32) -> *const c_char {
+    let message: &'static [u8] = match error {
+        0 => b"no error\0",
+        1 => b"a required pointer was NULL\0",
+        2 => b"not a valid BTI file\0",
+        3 => b"unsupported texture format\0",
+        4 => b"invalid argument\0",
+        5 => b"too many colors for the palette\0",
+        6 => b"internal error\0",
+        _ => b"unknown error\0",
+    };
+    message.as_ptr() as *const c_char
+}
+
+/// Parses `len` bytes of a BTI file, the data is copied.
+#[no_mangle]
+pub unsafe extern "C" fn bti_parse(data: *const u8, len: usize, out: *mut *mut BtiTexture) -> BtiError {
+    guard(|| {
+        if data.is_null() || out.is_null() {
+            return Err(BtiError::NullPointer);
+        }
+        let data = slice::from_raw_parts(data, len).to_vec();
+        let view = BtiView::parse(&data).map_err(|_| BtiError::InvalidData)?;
+        let bti = view.try_decode().map_err(|_| BtiError::UnsupportedFormat)?;
+        *out = Box::into_raw(Box::new(BtiTexture { data, bti }));
+        Ok(())
+    })
+}
+
+/// Releases a texture from bti_parse, NULL is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn bti_free(texture: *mut BtiTexture) {
+    if !texture.is_null() {
+        drop(Box::from_raw(texture));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bti_info(texture: *const BtiTexture, out: *mut BtiInfo) -> BtiError {
+    guard(|| {
+        let bti = &self::texture(texture)?.bti;
+        let out = out.as_mut().ok_or(BtiError::NullPointer)?;
+        *out = BtiInfo {
+            width: bti.width,
+            height: bti.height,
+            format: bti.format as u8,
+            alphasetting: bti.alphasetting,
+            wraps: bti.wraps as u8,
+            wrapt: bti.wrapt as u8,
+            paletteformat: bti.paletteformat as u8,
+            palettecount: bti.palettecount,
+            minfilter: bti.minfilter as u8,
+            magfilter: bti.magfilter as u8,
+            mipmapcount: bti.mipmapcount.max(1),
+            mipmaps_enabled: bti.mipmaps_enabled,
+            edge_lod: bti.edge_lod,
+            bias_clamp: bti.bias_clamp,
+            max_aniso: bti.max_aniso as u8,
+            min_lod: bti.min_lod,
+            max_lod: bti.max_lod,
+            lod_bias: bti.lod_bias,
+        };
+        Ok(())
+    })
+}
+
+/// Decodes one level to RGBA8 (or BGRA8 if `bgra` is true). The buffer of
+/// width * height * 4 bytes has to be released with bti_free_buffer.
+#[no_mangle]
+pub unsafe extern "C" fn bti_decode_level(texture: *const BtiTexture, level: u8, bgra: bool, out: *mut *mut u8,
+    outlen: *mut usize) -> BtiError {
+    guard(|| {
+        let texture = self::texture(texture)?;
+        if out.is_null() || outlen.is_null() {
+            return Err(BtiError::NullPointer);
+        }
+        let layout = if bgra { PixelLayout::Bgra } else { PixelLayout::Rgba };
+        let view = BtiView::parse(&texture.data).map_err(|_| BtiError::InvalidData)?;
+        if level >= view.levels() {
+            return Err(BtiError::InvalidArgument);
+        }
+        let pixels = match level {
+            0 if layout == PixelLayout::Rgba => texture.bti.rgbaimagedata.clone(),
+            _ => view.decode_level_layout(level, layout).map_err(|_| BtiError::UnsupportedFormat)?
+        };
+        givebuffer(pixels, out, outlen);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn bti_encode_options_default() -> BtiEncodeOptions {
+    BtiEncodeOptions {
+        format: -1,
+        paletteformat: PaletteFormats::RGB5A3 as u8,
+        wraps: WrapNodes::ClampToEdge as u8,
+        wrapt: WrapNodes::ClampToEdge as u8,
+        minfilter: FilterMode::Linear as u8,
+        magfilter: FilterMode::Linear as u8,
+        mipmaps: 1,
+    }
+}
+
+/// Encodes width * height RGBA8 pixels into a complete BTI file. `options` may be NULL
+/// for the defaults. The buffer has to be released with bti_free_buffer.
+#[no_mangle]
+pub unsafe extern "C" fn bti_encode(pixels: *const u8, width: u16, height: u16, options: *const BtiEncodeOptions,
+    out: *mut *mut u8, outlen: *mut usize) -> BtiError {
+    guard(|| {
+        if pixels.is_null() || out.is_null() || outlen.is_null() {
+            return Err(BtiError::NullPointer);
+        }
+        let options = options.as_ref().copied().unwrap_or(bti_encode_options_default());
+        let pixels = slice::from_raw_parts(pixels, width as usize * height as usize * 4).to_vec();
+        let paletteformat: PaletteFormats = enumvalue(options.paletteformat)?;
+        let mut builder = BtiBuilder::from_rgba(width.into(), height.into(), pixels)
+        .wrap(enumvalue(options.wraps)?, enumvalue(options.wrapt)?)
+        .filter(enumvalue(options.minfilter)?, enumvalue(options.magfilter)?)
+        .mipmaps(options.mipmaps.clamp(1, maxmipmaps(width, height)));
+        if options.format >= 0 {
+            let format = enumvalue(u8::try_from(options.format).map_err(|_| BtiError::InvalidArgument)?)?;
+            builder = builder.format(format);
+            if matches!(format, TextureFormats::C4 | TextureFormats::C8 | TextureFormats::C14X2) {
+                builder = builder.paletteformat(paletteformat);
+            }
+        }
+        let bti = builder.build().map_err(builderror)?;
+        let mut file = Cursor::new(vec![]);
+        bti.write_and_encode(&mut file);
+        givebuffer(file.into_inner(), out, outlen);
+        Ok(())
+    })
+}
+
+/// Releases a buffer from bti_decode_level or bti_encode, NULL is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn bti_free_buffer(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)));
+    }
+}
@@ -0,0 +1,315 @@
+// Python bindings, built with maturin into the `libbti` module.
+use std::fs;
+use std::io::Cursor;
+use libbti::prelude::*;
+use numpy::{PyArray1, PyArray3, PyArrayMethods, PyReadonlyArray3, PyUntypedArrayMethods};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+// Mirrors a libbti enum as a Python enum with the same variants and values.
+macro_rules! pyenum {
+    ($name:ident, $rust:ident, [$($variant:ident),+]) => {
+        #[pyclass(eq, eq_int)]
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        pub enum $name {
+            $($variant = $rust::$variant as isize),+
+        }
+
+        impl From<$rust> for $name {
+            fn from(value: $rust) -> Self {
+                match value {
+                    $($rust::$variant => $name::$variant),+
+                }
+            }
+        }
+
+        impl From<$name> for $rust {
+            fn from(value: $name) -> Self {
+                match value {
+                    $($name::$variant => $rust::$variant),+
+                }
+            }
+        }
+    };
+}
+
+pyenum!(TextureFormat, TextureFormats, [I4, I8, IA4, IA8, RGB565, RGB5A3, RGBA32, C4, C8, C14X2, CMPR]);
+pyenum!(WrapMode, WrapNodes, [ClampToEdge, Repeat, MirroredRepeat]);
+pyenum!(PaletteFormat, PaletteFormats, [IA8, RGB565, RGB5A3]);
+pyenum!(Filter, FilterMode, [Nearest, Linear, NearestMipmapNearest, NearestMipmapLinear,
+    LinearMipmapNearest, LinearMipmapLinear]);
+pyenum!(MaxAnisotropy, Anisotropy, [One, Two, Four]);
+
+fn valueerror(e: String) -> PyErr {
+    PyValueError::new_err(e)
+}
+
+#[pyclass(name = "BTI")]
+pub struct PyBti {
+    bti: BTI,
+}
+
+#[pymethods]
+impl PyBti {
+    #[staticmethod]
+    fn from_bytes(data: &[u8]) -> PyResult<Self> {
+        let view = BtiView::parse(data).map_err(valueerror)?;
+        Ok(Self { bti: view.try_decode().map_err(valueerror)? })
+    }
+
+    #[staticmethod]
+    fn load(path: std::path::PathBuf) -> PyResult<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    // Takes a (height, width, 4) uint8 array, the format is picked from the pixels like
+    // bti_extract does and can be changed afterwards.
+    #[staticmethod]
+    fn from_numpy(array: PyReadonlyArray3<u8>) -> PyResult<Self> {
+        let shape = array.shape();
+        if shape[2] != 4 || shape[0] > u32::MAX as usize || shape[1] > u32::MAX as usize {
+            return Err(valueerror(format!("Expected a (height, width, 4) RGBA array, got {:?}", shape)));
+        }
+        let pixels = array.as_array().iter().copied().collect();
+        let bti = BtiBuilder::from_rgba(shape[1] as u32, shape[0] as u32, pixels).build().map_err(|e| valueerror(e.to_string()))?;
+        Ok(Self { bti })
+    }
+
+    // RGBA pixels of one level as a (height, width, 4) uint8 array. Smaller levels come
+    // from the file if the pixels are unchanged, otherwise they are generated.
+    #[pyo3(signature = (level = 0))]
+    fn to_numpy<'py>(&self, py: Python<'py>, level: u8) -> PyResult<Bound<'py, PyArray3<u8>>> {
+        let bti = &self.bti;
+        if level >= bti.mipmapcount.max(1) {
+            return Err(valueerror(format!("Level {} requested, the texture has {}", level,
+                bti.mipmapcount.max(1))));
+        }
+        let (width, height) = levelsize(bti.width, bti.height, level);
+        let pixels = match level {
+            0 => bti.rgbaimagedata.clone(),
+            _ => {
+                self.check()?;
+                bti.decode_levels().swap_remove(level as usize)
+            }
+        };
+        PyArray1::from_vec(py, pixels).reshape([height as usize, width as usize, 4])
+    }
+
+    fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        self.check()?;
+        let mut out = Cursor::new(vec![]);
+        self.bti.write_and_encode(&mut out);
+        Ok(PyBytes::new(py, &out.into_inner()))
+    }
+
+    fn save(&self, py: Python<'_>, path: std::path::PathBuf) -> PyResult<()> {
+        Ok(fs::write(path, self.to_bytes(py)?.as_bytes())?)
+    }
+
+    // Builds the C4/C8 palette from the pixels.
+    fn generate_palette(&mut self) -> PyResult<()> {
+        match self.bti.generate_palette() {
+            true => Ok(()),
+            false => Err(valueerror(format!("The pixels don't fit into a {:?} palette", self.bti.format)))
+        }
+    }
+
+    #[getter]
+    fn width(&self) -> u16 {
+        self.bti.width
+    }
+
+    #[getter]
+    fn height(&self) -> u16 {
+        self.bti.height
+    }
+
+    #[getter]
+    fn palettecount(&self) -> u16 {
+        self.bti.palettecount
+    }
+
+    #[getter]
+    fn format(&self) -> TextureFormat {
+        self.bti.format.into()
+    }
+
+    #[setter]
+    fn set_format(&mut self, value: TextureFormat) {
+        self.bti.format = value.into();
+    }
+
+    #[getter]
+    fn paletteformat(&self) -> PaletteFormat {
+        self.bti.paletteformat.into()
+    }
+
+    #[setter]
+    fn set_paletteformat(&mut self, value: PaletteFormat) {
+        self.bti.paletteformat = value.into();
+    }
+
+    #[getter]
+    fn wraps(&self) -> WrapMode {
+        self.bti.wraps.into()
+    }
+
+    #[setter]
+    fn set_wraps(&mut self, value: WrapMode) {
+        self.bti.wraps = value.into();
+    }
+
+    #[getter]
+    fn wrapt(&self) -> WrapMode {
+        self.bti.wrapt.into()
+    }
+
+    #[setter]
+    fn set_wrapt(&mut self, value: WrapMode) {
+        self.bti.wrapt = value.into();
+    }
+
+    #[getter]
+    fn minfilter(&self) -> Filter {
+        self.bti.minfilter.into()
+    }
+
+    #[setter]
+    fn set_minfilter(&mut self, value: Filter) {
+        self.bti.minfilter = value.into();
+    }
+
+    #[getter]
+    fn magfilter(&self) -> Filter {
+        self.bti.magfilter.into()
+    }
+
+    #[setter]
+    fn set_magfilter(&mut self, value: Filter) {
+        self.bti.magfilter = value.into();
+    }
+
+    #[getter]
+    fn alphasetting(&self) -> u8 {
+        self.bti.alphasetting
+    }
+
+    #[setter]
+    fn set_alphasetting(&mut self, value: u8) {
+        self.bti.alphasetting = value;
+    }
+
+    #[getter]
+    fn mipmapcount(&self) -> u8 {
+        self.bti.mipmapcount
+    }
+
+    // Clamped to the number of levels the size allows, mipmaps_enabled and max_lod
+    // follow it.
+    #[setter]
+    fn set_mipmapcount(&mut self, value: u8) {
+        self.bti.mipmapcount = value.clamp(1, maxmipmaps(self.bti.width, self.bti.height));
+        self.bti.mipmaps_enabled = self.bti.mipmapcount > 1;
+        self.bti.max_lod = (self.bti.mipmapcount - 1) as f32;
+    }
+
+    #[getter]
+    fn mipmaps_enabled(&self) -> bool {
+        self.bti.mipmaps_enabled
+    }
+
+    #[setter]
+    fn set_mipmaps_enabled(&mut self, value: bool) {
+        self.bti.mipmaps_enabled = value;
+    }
+
+    #[getter]
+    fn min_lod(&self) -> f32 {
+        self.bti.min_lod
+    }
+
+    #[setter]
+    fn set_min_lod(&mut self, value: f32) {
+        self.bti.min_lod = value;
+    }
+
+    #[getter]
+    fn max_lod(&self) -> f32 {
+        self.bti.max_lod
+    }
+
+    #[setter]
+    fn set_max_lod(&mut self, value: f32) {
+        self.bti.max_lod = value;
+    }
+
+    #[getter]
+    fn lod_bias(&self) -> f32 {
+        self.bti.lod_bias
+    }
+
+    #[setter]
+    fn set_lod_bias(&mut self, value: f32) {
+        self.bti.lod_bias = value;
+    }
+
+    #[getter]
+    fn edge_lod(&self) -> bool {
+        self.bti.edge_lod
+    }
+
+    #[setter]
+    fn set_edge_lod(&mut self, value: bool) {
+        self.bti.edge_lod = value;
+    }
+
+    #[getter]
+    fn bias_clamp(&self) -> bool {
+        self.bti.bias_clamp
+    }
+
+    #[setter]
+    fn set_bias_clamp(&mut self, value: bool) {
+        self.bti.bias_clamp = value;
+    }
+
+    #[getter]
+    fn max_aniso(&self) -> MaxAnisotropy {
+        self.bti.max_aniso.into()
+    }
+
+    #[setter]
+    fn set_max_aniso(&mut self, value: MaxAnisotropy) {
+        self.bti.max_aniso = value.into();
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<BTI {:?} {}x{}, {} mips>", self.bti.format, self.bti.width, self.bti.height,
+            self.bti.mipmapcount.max(1))
+    }
+}
+
+impl PyBti {
+    // Turns the cases the encoder would panic on into Python errors.
+    fn check(&self) -> PyResult<()> {
+        match self.bti.format {
+            TextureFormats::C14X2 => Err(valueerror("C14X2 textures are not supported".to_string())),
+            TextureFormats::C4 | TextureFormats::C8 if self.bti.palettecount == 0 =>
+                Err(valueerror("C4/C8 textures need a palette, see generate_palette".to_string())),
+            _ => Ok(())
+        }
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "libbti")]
+fn libbti_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyBti>()?;
+    m.add_class::<TextureFormat>()?;
+    m.add_class::<WrapMode>()?;
+    m.add_class::<PaletteFormat>()?;
+    m.add_class::<Filter>()?;
+    m.add_class::<MaxAnisotropy>()?;
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use std::io::Cursor;
+use libbti::prelude::*;
+use wasm_bindgen::prelude::*;
+
+// First level of a BTI, decoded to RGBA.
+#[wasm_bindgen(getter_with_clone)]
+pub struct Texture {
+    pub width: u16,
+    pub height: u16,
+    pub format: String,
+    pub paletteformat: String,
+    pub mipmaps: u8,
+    pub mipmaps_enabled: bool,
+    pub edge_lod: bool,
+    pub bias_clamp: bool,
+    pub max_aniso: String,
+    // LOD range and bias in levels.
+    pub min_lod: f32,
+    pub max_lod: f32,
+    pub lod_bias: f32,
+    // RGBA8 pixels row by row, a Uint8Array on the JS side.
+    pub pixels: Vec<u8>,
+}
+
+// Settings for `encode`, the empty strings pick the same defaults bti_extract uses.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Default)]
+pub struct EncodeSettings {
+    // Texture format name such as "CMPR", empty to pick one from the pixels.
+    pub format: String,
+    // Only valid for the palette formats C4, C8 and C14X2.
+    pub paletteformat: String,
+    pub wraps: String,
+    pub wrapt: String,
+    pub minfilter: String,
+    pub magfilter: String,
+    // Number of levels including the full size one, 0 and 1 both mean no mipmaps.
+    pub mipmaps: u8,
+}
+
+#[wasm_bindgen]
+impl EncodeSettings {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+// None for an empty string, which keeps the default.
+fn setting<T: std::str::FromStr<Err = String>>(value: &str) -> Result<Option<T>, String> {
+    match value.is_empty() {
+        true => Ok(None),
+        false => value.parse().map(Some)
+    }
+}
+
+// Parses a BTI file, broken files are errors instead of panics.
+#[wasm_bindgen]
+pub fn parse(data: &[u8]) -> Result<Texture, JsError> {
+    let view = BtiView::parse(data).map_err(|e| JsError::new(&e))?;
+    let pixels = view.decode_level(0).map_err(|e| JsError::new(&e))?;
+    let mipmaps = view.levels();
+    let header = view.header;
+    Ok(Texture {
+        width: header.width,
+        height: header.height,
+        format: format!("{:?}", header.format),
+        paletteformat: format!("{:?}", header.paletteformat),
+        mipmaps,
+        mipmaps_enabled: header.mipmaps_enabled,
+        edge_lod: header.edge_lod,
+        bias_clamp: header.bias_clamp,
+        max_aniso: format!("{:?}", header.max_aniso),
+        min_lod: header.min_lod,
+        max_lod: header.max_lod,
+        lod_bias: header.lod_bias,
+        pixels,
+    })
+}
+
+// Encodes RGBA8 pixels into a complete BTI file.
+#[wasm_bindgen]
+pub fn encode(pixels: &[u8], width: u16, height: u16, settings: &EncodeSettings) -> Result<Vec<u8>, JsError> {
+    buildbti(pixels, width, height, settings).map_err(|e| JsError::new(&e))
+}
+
+fn buildbti(pixels: &[u8], width: u16, height: u16, settings: &EncodeSettings) -> Result<Vec<u8>, String> {
+    let mut builder = BtiBuilder::from_rgba(width.into(), height.into(), pixels.to_vec())
+    .mipmaps(settings.mipmaps.clamp(1, maxmipmaps(width, height)));
+    if let Some(format) = setting(&settings.format)? {
+        builder = builder.format(format);
+    }
+    if let Some(paletteformat) = setting(&settings.paletteformat)? {
+        builder = builder.paletteformat(paletteformat);
+    }
+    if let Some(wraps) = setting(&settings.wraps)? {
+        builder = builder.wraps(wraps);
+    }
+    if let Some(wrapt) = setting(&settings.wrapt)? {
+        builder = builder.wrapt(wrapt);
+    }
+    if let Some(minfilter) = setting(&settings.minfilter)? {
+        builder = builder.minfilter(minfilter);
+    }
+    if let Some(magfilter) = setting(&settings.magfilter)? {
+        builder = builder.magfilter(magfilter);
+    }
+    let bti = builder.build().map_err(|e| e.to_string())?;
+    let mut out = Cursor::new(vec![]);
+    bti.write_and_encode(&mut out);
+    Ok(out.into_inner())
+}
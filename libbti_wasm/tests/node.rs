@@ -0,0 +1,37 @@
+// Run with `wasm-pack test --node`, the tests only exist for wasm32.
+#![cfg(target_arch = "wasm32")]
+use libbti_wasm::*;
+use wasm_bindgen_test::*;
+
+fn gradient(width: u16, height: u16) -> Vec<u8> {
+    (0..height).flat_map(|y| (0..width).flat_map(move |x| [(x * 4) as u8, (y * 4) as u8, 0x80, 0xFF]))
+    .collect()
+}
+
+#[wasm_bindgen_test]
+fn encode_and_parse() {
+    let pixels = gradient(32, 16);
+    let settings = EncodeSettings { format: "RGBA32".to_string(), mipmaps: 3, ..EncodeSettings::new() };
+    let data = encode(&pixels, 32, 16, &settings).unwrap();
+    let texture = parse(&data).unwrap();
+    assert_eq!((texture.width, texture.height, texture.mipmaps), (32, 16, 3));
+    assert_eq!(texture.format, "RGBA32");
+    assert_eq!(texture.pixels, pixels);
+}
+
+#[wasm_bindgen_test]
+fn palette_formats() {
+    let pixels = gradient(4, 4);
+    let settings = EncodeSettings { format: "c8".to_string(), paletteformat: "rgb5a3".to_string(),
+        ..EncodeSettings::new() };
+    let texture = parse(&encode(&pixels, 4, 4, &settings).unwrap()).unwrap();
+    assert_eq!((texture.format.as_str(), texture.paletteformat.as_str()), ("C8", "RGB5A3"));
+}
+
+#[wasm_bindgen_test]
+fn errors() {
+    assert!(parse(&[0u8; 8]).is_err());
+    assert!(encode(&[0u8; 12], 2, 2, &EncodeSettings::new()).is_err());
+    let settings = EncodeSettings { format: "ETC2".to_string(), ..EncodeSettings::new() };
+    assert!(encode(&gradient(4, 4), 4, 4, &settings).is_err());
+}